@@ -0,0 +1,397 @@
+use crate::interpreter::Value;
+use crate::error::*;
+use std::hash::Hasher;
+use std::rc::Rc;
+
+// Names of the functions provided by the interpreter itself. A user defined function with the
+// same name takes precedence over the builtin. Builtins that need interpreter state (like input,
+// print, getenv, is_defined, filter, count, reduce and the operator functions) are handled by the interpreter instead of call_builtin.
+const BUILTINS: &[&str] = &["repeat", "input", "keys", "values", "error", "starts_with", "ends_with",
+    "trim", "to_upper", "to_lower", "range", "format", "is_defined", "zip",
+    "head", "tail", "append", "set", "reverse", "to_base", "filter", "count",
+    "saturating_add", "saturating_sub", "abs_diff", "type", "is_number", "is_string", "is_bool",
+    "reduce", "add", "sub", "mul", "div", "as_int", "as_float", "getenv", "hash", "print"];
+
+// The arithmetic operators that can also be used as functions of two arguments, by these names.
+const OPERATOR_FUNCTIONS: &[&str] = &["add", "sub", "mul", "div"];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name)
+}
+
+pub fn is_operator_function(name: &str) -> bool {
+    OPERATOR_FUNCTIONS.contains(&name)
+}
+
+// Calls the builtin with the given name. The arguments have already been evaluated by the caller.
+pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match name {
+        "repeat" => repeat(args),
+        "keys" => keys(args),
+        "values" => values(args),
+        "error" => error(args),
+        "starts_with" => starts_with(args),
+        "ends_with" => ends_with(args),
+        "trim" => trim(args),
+        "to_upper" => to_upper(args),
+        "to_lower" => to_lower(args),
+        "range" => range(args),
+        "format" => format(args),
+        "zip" => zip(args),
+        "head" => head(args),
+        "tail" => tail(args),
+        "append" => append(args),
+        "set" => set(args),
+        "reverse" => reverse(args),
+        "to_base" => to_base(args),
+        "saturating_add" | "saturating_sub" | "abs_diff" => saturating(name, args),
+        "type" | "is_number" | "is_string" | "is_bool" => type_of(name, args),
+        "as_int" => as_int(args),
+        "as_float" => as_float(args),
+        "hash" => hash(args),
+        _ => Err(AsaErrorKind::UndefinedFunction(name.to_string())),
+    }
+}
+
+// repeat(s, n) returns the string s repeated n times.
+fn repeat(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::String(s), Value::Number(n)] => {
+            if *n < 0 {
+                return Err(
+                    AsaErrorKind::Generic(format!("repeat count must not be negative, got {}", n))
+                );
+            }
+            Ok(Value::String(s.repeat(*n as usize)))
+        }
+        _ => Err(AsaErrorKind::TypeMismatch("repeat expects a string and a number".to_string())),
+    }
+}
+
+// keys(m) returns the keys of a map as a list of strings, in sorted order.
+fn keys(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::Map(map)] => Ok(Value::List(map.keys().cloned().map(Value::String).collect())),
+        _ => Err(AsaErrorKind::TypeMismatch("keys expects a map".to_string())),
+    }
+}
+
+// values(m) returns the values of a map as a list, in the sorted order of their keys.
+fn values(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::Map(map)] => Ok(Value::List(map.values().cloned().collect())),
+        _ => Err(AsaErrorKind::TypeMismatch("values expects a map".to_string())),
+    }
+}
+
+// error(msg) aborts the evaluation with a UserError carrying msg.
+fn error(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::String(message)] => Err(AsaErrorKind::UserError(message.clone())),
+        _ => Err(AsaErrorKind::TypeMismatch("error expects a string message".to_string())),
+    }
+}
+
+// starts_with(s, prefix) tells whether the string s begins with prefix.
+fn starts_with(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::String(s), Value::String(prefix)] => Ok(Value::Bool(s.starts_with(prefix.as_str()))),
+        _ => Err(AsaErrorKind::TypeMismatch("starts_with expects two strings".to_string())),
+    }
+}
+
+// ends_with(s, suffix) tells whether the string s ends with suffix.
+fn ends_with(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::String(s), Value::String(suffix)] => Ok(Value::Bool(s.ends_with(suffix.as_str()))),
+        _ => Err(AsaErrorKind::TypeMismatch("ends_with expects two strings".to_string())),
+    }
+}
+
+// trim(s) returns s without its leading and trailing whitespace.
+fn trim(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::String(s)] => Ok(Value::String(s.trim().to_string())),
+        _ => Err(AsaErrorKind::TypeMismatch("trim expects a string".to_string())),
+    }
+}
+
+// to_upper(s) returns s with every letter in upper case.
+fn to_upper(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::String(s)] => Ok(Value::String(s.to_uppercase())),
+        _ => Err(AsaErrorKind::TypeMismatch("to_upper expects a string".to_string())),
+    }
+}
+
+// to_lower(s) returns s with every letter in lower case.
+fn to_lower(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::String(s)] => Ok(Value::String(s.to_lowercase())),
+        _ => Err(AsaErrorKind::TypeMismatch("to_lower expects a string".to_string())),
+    }
+}
+
+// range(start, end) returns the numbers from start up to but not including end. The list is
+// empty when end is not greater than start.
+fn range(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::Number(start), Value::Number(end)] => Ok(Value::List((*start..*end).map(Value::Number).collect())),
+        _ => Err(AsaErrorKind::TypeMismatch("range expects two numbers".to_string())),
+    }
+}
+
+// format(template, ...args) replaces each {} in the template with the next argument, in order.
+fn format(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    let (template, values) = match args {
+        [Value::String(template), values @ ..] => (template, values),
+        _ => {
+            return Err(AsaErrorKind::TypeMismatch("format expects a template string".to_string()));
+        }
+    };
+    let pieces: Vec<&str> = template.split("{}").collect();
+    if pieces.len() - 1 != values.len() {
+        return Err(
+            AsaErrorKind::Generic(
+                format!(
+                    "format template has {} placeholders but was given {} arguments",
+                    pieces.len() - 1,
+                    values.len()
+                )
+            )
+        );
+    }
+    let mut result = pieces[0].to_string();
+    for (value, piece) in values.iter().zip(&pieces[1..]) {
+        result.push_str(&value.to_string());
+        result.push_str(piece);
+    }
+    Ok(Value::String(result))
+}
+
+// zip(xs, ys) pairs up the elements of two lists as two-element lists, stopping at the end of the shorter one.
+fn zip(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::List(xs), Value::List(ys)] => Ok(Value::List(
+            xs.iter().zip(ys).map(|(x, y)| Value::List(vec![x.clone(), y.clone()])).collect()
+        )),
+        _ => Err(AsaErrorKind::TypeMismatch("zip expects two lists".to_string())),
+    }
+}
+
+// head(xs) returns the first element of a list, it is an error when the list is empty.
+fn head(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::List(xs)] => xs.first().cloned().ok_or(AsaErrorKind::IndexOutOfBounds),
+        _ => Err(AsaErrorKind::TypeMismatch("head expects a list".to_string())),
+    }
+}
+
+// tail(xs) returns the list without its first element. The tail of an empty list is empty.
+fn tail(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::List(xs)] => Ok(Value::List(xs.iter().skip(1).cloned().collect())),
+        _ => Err(AsaErrorKind::TypeMismatch("tail expects a list".to_string())),
+    }
+}
+
+// append(xs, v) returns a new list with v added at the end of xs.
+fn append(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::List(xs), value] => {
+            let mut list = xs.clone();
+            list.push(value.clone());
+            Ok(Value::List(list))
+        }
+        _ => Err(AsaErrorKind::TypeMismatch("append expects a list and a value".to_string())),
+    }
+}
+
+// set(xs, i, v) returns a new list with the element at index i of xs replaced by v. It is an
+// error when i is not an index of xs.
+fn set(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::List(xs), Value::Number(i), value] => {
+            let mut list = xs.clone();
+            let slot = usize::try_from(*i).ok()
+                .and_then(|i| list.get_mut(i))
+                .ok_or(AsaErrorKind::IndexOutOfBounds)?;
+            *slot = value.clone();
+            Ok(Value::List(list))
+        }
+        _ => Err(AsaErrorKind::TypeMismatch("set expects a list, an index and a value".to_string())),
+    }
+}
+
+// reverse(x) returns a string with its characters in reverse order, or a list with its elements in reverse order.
+fn reverse(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::String(s)] => Ok(Value::String(s.chars().rev().collect())),
+        [Value::List(xs)] => Ok(Value::List(xs.iter().rev().cloned().collect())),
+        _ => Err(AsaErrorKind::TypeMismatch("reverse expects a string or a list".to_string())),
+    }
+}
+
+// to_base(n, base) writes the integer n in base 2, 8, 10 or 16, with lowercase digits and a leading - when it is negative.
+fn to_base(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    let (n, base) = match args {
+        [Value::Number(n), Value::Number(base)] => (*n, *base),
+        _ => {
+            return Err(AsaErrorKind::TypeMismatch("to_base expects two numbers".to_string()));
+        }
+    };
+    let magnitude = (n as i64).unsigned_abs();
+    let digits = match base {
+        2 => format!("{:b}", magnitude),
+        8 => format!("{:o}", magnitude),
+        10 => format!("{}", magnitude),
+        16 => format!("{:x}", magnitude),
+        _ => {
+            return Err(AsaErrorKind::Generic(format!("Unsupported base {}, expected 2, 8, 10 or 16", base)));
+        }
+    };
+    let sign = if n < 0 { "-" } else { "" };
+    Ok(Value::String(format!("{}{}", sign, digits)))
+}
+
+// saturating_add(a, b) and saturating_sub(a, b) stop at the largest or smallest number instead of
+// overflowing, and abs_diff(a, b) is the distance between a and b, at most the largest number.
+fn saturating(name: &str, args: &[Value]) -> Result<Value, AsaErrorKind> {
+    let (a, b) = match args {
+        [Value::Number(a), Value::Number(b)] => (*a, *b),
+        _ => {
+            return Err(AsaErrorKind::TypeMismatch(format!("{} expects two integers", name)));
+        }
+    };
+    let result = match name {
+        "saturating_add" => a.saturating_add(b),
+        "saturating_sub" => a.saturating_sub(b),
+        _ => a.abs_diff(b).min(i32::MAX as u32) as i32,
+    };
+    Ok(Value::Number(result))
+}
+
+// type(x) is the name of the type of x, as written in a type annotation. is_number(x), is_string(x)
+// and is_bool(x) tell whether x has that type, integers and floats are both numbers.
+fn type_of(name: &str, args: &[Value]) -> Result<Value, AsaErrorKind> {
+    let value = match args {
+        [value] => value,
+        _ => {
+            return Err(AsaErrorKind::ArityMismatch { expected: 1, found: args.len() });
+        }
+    };
+    let type_name = value.type_name();
+    match name {
+        "type" => Ok(Value::String(type_name.to_string())),
+        _ => Ok(Value::Bool(name.strip_prefix("is_") == Some(type_name))),
+    }
+}
+
+// as_int(x) is the number x without its fractional part, rounded toward zero. A float outside the
+// range of integers overflows or underflows.
+fn as_int(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::Number(n)] => Ok(Value::Number(*n)),
+        [Value::Float(f)] if f.is_nan() => Err(AsaErrorKind::Generic("NaN has no integer value".to_string())),
+        [Value::Float(f)] if f.trunc() > i32::MAX as f64 => Err(AsaErrorKind::NumberOverflow),
+        [Value::Float(f)] if f.trunc() < i32::MIN as f64 => Err(AsaErrorKind::NumberUnderflow),
+        [Value::Float(f)] => Ok(Value::Number(f.trunc() as i32)),
+        _ => Err(AsaErrorKind::TypeMismatch("as_int expects a number".to_string())),
+    }
+}
+
+// as_float(x) is the number x as a float.
+fn as_float(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [Value::Number(n)] => Ok(Value::Float(*n as f64)),
+        [Value::Float(f)] => Ok(Value::Float(*f)),
+        _ => Err(AsaErrorKind::TypeMismatch("as_float expects a number".to_string())),
+    }
+}
+
+// hash(x) is a number computed from x that is the same for values == finds equal, such as 1 and
+// 1.0, in every run of every interpreter on every platform. It is the 32 bit FNV-1a hash of the bytes write_value writes for
+// the value. A function is hashed by its identity, so its hash only holds for as long as the
+// function exists.
+fn hash(args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match args {
+        [value] => {
+            let mut hasher = Fnv1a(0x811c9dc5);
+            write_value(&mut hasher, value);
+            Ok(Value::Number(hasher.finish() as u32 as i32))
+        }
+        _ => Err(AsaErrorKind::ArityMismatch { expected: 1, found: args.len() }),
+    }
+}
+
+// Writes a tag byte for the variant of a value followed by its contents: numbers by their
+// little-endian bytes, a float by those of its bits, and a string, list or map by its length as
+// eight little-endian bytes followed by its bytes or elements. A float equal to a number is
+// written as that number.
+fn write_value(hasher: &mut Fnv1a, value: &Value) {
+    match value {
+        Value::Float(f) if f.fract() == 0.0 && *f >= i32::MIN as f64 && *f <= i32::MAX as f64 =>
+            write_value(hasher, &Value::Number(*f as i32)),
+        Value::Null => hasher.write(&[0]),
+        Value::Bool(b) => hasher.write(&[1, *b as u8]),
+        Value::Number(n) => {
+            hasher.write(&[2]);
+            hasher.write(&n.to_le_bytes());
+        }
+        Value::Float(f) => {
+            hasher.write(&[3]);
+            hasher.write(&f.to_bits().to_le_bytes());
+        }
+        Value::String(s) => {
+            hasher.write(&[4]);
+            write_str(hasher, s);
+        }
+        Value::List(list) => {
+            hasher.write(&[5]);
+            hasher.write(&(list.len() as u64).to_le_bytes());
+            for element in list {
+                write_value(hasher, element);
+            }
+        }
+        Value::Map(map) => {
+            hasher.write(&[6]);
+            hasher.write(&(map.len() as u64).to_le_bytes());
+            for (key, value) in map {
+                write_str(hasher, key);
+                write_value(hasher, value);
+            }
+        }
+        Value::Function(function, name) => {
+            hasher.write(&[7]);
+            hasher.write(&(Rc::as_ptr(function) as usize as u64).to_le_bytes());
+            match name {
+                Some(name) => {
+                    hasher.write(&[1]);
+                    write_str(hasher, name);
+                }
+                None => hasher.write(&[0]),
+            }
+        }
+    }
+}
+
+// Writes a string as its length followed by its bytes, see write_value.
+fn write_str(hasher: &mut Fnv1a, s: &str) {
+    hasher.write(&(s.len() as u64).to_le_bytes());
+    hasher.write(s.as_bytes());
+}
+
+// The FNV-1a hash function, with its fixed 32 bit offset basis as the starting state.
+struct Fnv1a(u32);
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u32).wrapping_mul(0x01000193);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0 as u64
+    }
+}
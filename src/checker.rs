@@ -0,0 +1,224 @@
+use crate::parser::Node;
+use crate::error::*;
+use crate::builtins;
+use crate::visitor::{Visitor, walk};
+use std::collections::{HashMap, HashSet};
+
+// A best-effort static pass over a program. It reports the errors that are certain to happen
+// when the code runs, without running anything: variables read before they are defined, calls to
+// unknown functions, calls with the wrong number of arguments and literals of the wrong type in
+// arithmetic. Errors are collected rather than stopping at the first one.
+pub struct Checker {
+    // Known functions and the numbers of parameters of their definitions, one per overload.
+    arities: HashMap<String, Vec<usize>>,
+    // Variables defined so far in the scope being checked. Like at runtime, a function only sees its own parameters and locals.
+    scopes: Vec<HashSet<String>>,
+    // The variables defined at the top of the program, which functions can read however far down they are defined.
+    globals: HashSet<String>,
+    pub errors: Vec<AsaErrorKind>,
+}
+
+impl Checker {
+    pub fn new(arities: HashMap<String, Vec<usize>>, globals: HashSet<String>) -> Checker {
+        Checker { arities, scopes: vec![globals.clone()], globals, errors: vec![] }
+    }
+
+    // Registers the functions and global variables defined at the top of the program, so functions
+    // defined further up know about them.
+    pub fn define_functions(&mut self, node: &Node) {
+        if let Node::Program { children } = node {
+            for child in children {
+                match child {
+                    Node::FunctionDefine { name, children } => {
+                        let params = children.first().map_or(0, |params| params.children().len());
+                        let arities = self.arities.entry(String::from_utf8_lossy(name).to_string()).or_default();
+                        if !arities.contains(&params) {
+                            arities.push(params);
+                        }
+                    }
+                    Node::VariableDefine { children }
+                    | Node::ConstDefine { children }
+                    | Node::RecursiveDefine { children }
+                    | Node::Destructure { children } => {
+                        for name in children {
+                            if let Node::Identifier { value } = name {
+                                self.globals.insert(String::from_utf8_lossy(value).to_string());
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn define(&mut self, name: &[u8]) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(String::from_utf8_lossy(name).to_string());
+        }
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.scopes.last().is_some_and(|scope| scope.contains(name))
+            || (self.scopes.len() > 1 && self.globals.contains(name))
+    }
+
+    // Checks the body of a function or of a function value in a scope of its own that starts with
+    // the parameters, and with the function's own name for one bound with let rec.
+    fn visit_function_body(&mut self, children: &[Node], itself: Option<&[u8]>) {
+        let mut scope = HashSet::new();
+        if let Some(params) = children.first() {
+            for param in params.children() {
+                let param = match param {
+                    Node::Parameter { children } if !children.is_empty() => &children[0],
+                    _ => param,
+                };
+                if let Node::Identifier { value } = literal(param) {
+                    scope.insert(String::from_utf8_lossy(value).to_string());
+                }
+            }
+        }
+        if let Some(name) = itself {
+            scope.insert(String::from_utf8_lossy(name).to_string());
+        }
+        self.scopes.push(scope);
+        for statements in children.iter().skip(1) {
+            self.visit_node(statements);
+        }
+        self.scopes.pop();
+    }
+}
+
+// The node a literal operand would be, looking through expression and span wrappers.
+fn literal(node: &Node) -> &Node {
+    match node.unspanned() {
+        Node::Expression { children } if children.len() == 1 => literal(&children[0]),
+        node => node,
+    }
+}
+
+fn is_non_number_literal(node: &Node) -> bool {
+    matches!(
+        literal(node),
+        Node::String { .. } | Node::Bool { .. } | Node::Map { .. } | Node::List { .. }
+    )
+}
+
+impl Visitor for Checker {
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::FunctionDefine { .. } => self.visit_function_define(node),
+            Node::FunctionCall { .. } => self.visit_function_call(node),
+            Node::VariableDefine { .. } | Node::ConstDefine { .. } => self.visit_variable_define(node),
+            Node::Identifier { .. } => self.visit_identifier(node),
+            Node::Lambda { children } => self.visit_function_body(children, None),
+            Node::Destructure { children } => {
+                if let Some((value, names)) = children.split_last() {
+                    self.visit_node(value);
+                    for name in names {
+                        if let Node::Identifier { value } = name {
+                            self.define(value);
+                        }
+                    }
+                }
+            }
+            Node::RecursiveDefine { children } => {
+                if let [Node::Identifier { value: name }, value] = children.as_slice() {
+                    self.define(name);
+                    match literal(value) {
+                        Node::Lambda { children } => self.visit_function_body(children, Some(name)),
+                        value => self.visit_node(value),
+                    }
+                }
+            }
+            Node::WhileLet { children } => {
+                if let [Node::Identifier { value: name }, value, body] = children.as_slice() {
+                    self.visit_node(value);
+                    self.define(name);
+                    self.visit_node(body);
+                }
+            }
+            // only a variable can be assigned, not something like `5 = x` or `foo() = 1`
+            Node::Assignment { children } => {
+                if !matches!(children.first(), Some(Node::Identifier { .. })) {
+                    self.errors.push(AsaErrorKind::Generic("invalid assignment target".to_string()));
+                }
+                walk(self, node);
+            }
+            Node::MathExpression { name, children } => {
+                if let b"add" | b"sub" | b"mul" | b"div" = name.as_slice() {
+                    if children.iter().any(is_non_number_literal) {
+                        self.errors.push(
+                            AsaErrorKind::TypeMismatch("MathExpression operands must be numbers".to_string())
+                        );
+                    }
+                }
+                walk(self, node);
+            }
+            Node::UnaryExpression { name, children } => {
+                if children.iter().any(is_non_number_literal) {
+                    let message = if name == b"pos" {
+                        "Only numbers can take a unary plus"
+                    } else {
+                        "Only numbers can be negated"
+                    };
+                    self.errors.push(AsaErrorKind::TypeMismatch(message.to_string()));
+                }
+                walk(self, node);
+            }
+            _ => walk(self, node),
+        }
+    }
+
+    fn visit_function_define(&mut self, node: &Node) {
+        self.visit_function_body(node.children(), None);
+    }
+
+    fn visit_function_call(&mut self, node: &Node) {
+        if let Node::FunctionCall { name, children } = node {
+            let function_name = String::from_utf8_lossy(name).to_string();
+            match self.arities.get(&function_name).map(|arities| arities.as_slice()) {
+                // a variable may hold a function value, whose parameters are only known when it runs
+                _ if self.is_defined(&function_name) => {}
+                Some(arities) if arities.contains(&children.len()) => {}
+                Some([params]) => {
+                    self.errors.push(AsaErrorKind::ArityMismatch { expected: *params, found: children.len() });
+                }
+                Some(_) => self.errors.push(AsaErrorKind::NoMatchingOverload(function_name.clone())),
+                None if builtins::is_builtin(&function_name) => {}
+                None => self.errors.push(AsaErrorKind::UndefinedFunction(function_name)),
+            }
+            // the name of a named argument is not a variable, only its value is checked
+            for arg in children {
+                match arg {
+                    Node::NamedArgument { children } => {
+                        if let Some(value) = children.get(1) {
+                            self.visit_node(value);
+                        }
+                    }
+                    _ => self.visit_node(arg),
+                }
+            }
+        }
+    }
+
+    // The value is checked before the variable is defined, so `let x = x;` reads an undefined x.
+    fn visit_variable_define(&mut self, node: &Node) {
+        let children = node.children();
+        for value in children.iter().skip(1) {
+            self.visit_node(value);
+        }
+        if let Some(Node::Identifier { value }) = children.first() {
+            self.define(value);
+        }
+    }
+
+    fn visit_identifier(&mut self, node: &Node) {
+        if let Node::Identifier { value } = node {
+            let name = String::from_utf8_lossy(value).to_string();
+            if !self.is_defined(&name) && !builtins::is_operator_function(&name) {
+                self.errors.push(AsaErrorKind::VariableNotDefined(name));
+            }
+        }
+    }
+}
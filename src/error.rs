@@ -1,9 +1,49 @@
-#[derive(Debug,PartialEq)]
-pub enum AsaErrorKind {
-  UndefinedFunction,
-  VariableNotDefined(String),
-  DivisionByZero,
-  NumberOverflow,
-  NumberUnderflow,
-  Generic(String),  
-}
\ No newline at end of file
+#[derive(Debug,PartialEq)]
+pub enum AsaErrorKind {
+  UndefinedFunction(String),
+  DuplicateFunction(String),
+  NoMatchingOverload(String),
+  // a function was called with a different number of arguments than it takes
+  ArityMismatch { expected: usize, found: usize },
+  KeyNotFound(String),
+  Forbidden(String),
+  TypeMismatch(String),
+  UserError(String),
+  ParseError(String),
+  StepLimitExceeded,
+  MemoryLimitExceeded,
+  NoMatch,
+  IndexOutOfBounds,
+  VariableNotDefined(String),
+  DivisionByZero,
+  NumberOverflow,
+  NumberUnderflow,
+  // for errors none of the variants above describe, like malformed trees and I/O failures
+  Generic(String),
+}
+
+// The message an error is reported with to the person running a program.
+impl std::fmt::Display for AsaErrorKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AsaErrorKind::UndefinedFunction(name) => write!(f, "undefined function {}", name),
+      AsaErrorKind::DuplicateFunction(name) => write!(f, "function {} is defined more than once", name),
+      AsaErrorKind::NoMatchingOverload(name) => write!(f, "no definition of {} takes these arguments", name),
+      AsaErrorKind::ArityMismatch { expected, found } => write!(f, "expected {} arguments, found {}", expected, found),
+      AsaErrorKind::KeyNotFound(key) => write!(f, "key {:?} not found", key),
+      AsaErrorKind::Forbidden(name) => write!(f, "{} is not allowed in sandbox mode", name),
+      AsaErrorKind::TypeMismatch(message)
+      | AsaErrorKind::UserError(message)
+      | AsaErrorKind::ParseError(message)
+      | AsaErrorKind::Generic(message) => write!(f, "{}", message),
+      AsaErrorKind::StepLimitExceeded => write!(f, "step limit exceeded"),
+      AsaErrorKind::MemoryLimitExceeded => write!(f, "memory limit exceeded"),
+      AsaErrorKind::NoMatch => write!(f, "no match arm matched the value"),
+      AsaErrorKind::IndexOutOfBounds => write!(f, "index out of bounds"),
+      AsaErrorKind::VariableNotDefined(name) => write!(f, "variable {} is not defined", name),
+      AsaErrorKind::DivisionByZero => write!(f, "division by zero"),
+      AsaErrorKind::NumberOverflow => write!(f, "number overflow"),
+      AsaErrorKind::NumberUnderflow => write!(f, "number underflow"),
+    }
+  }
+}
@@ -1,24 +1,559 @@
-use crate::parser::Node;
-use std::collections::HashMap;
+use crate::parser::{Node, Span, program};
+use crate::lexer::{lex, validate, with_furthest_failure, Token};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::io::{BufRead, Write};
 use crate::error::*;
+use crate::builtins;
+use crate::checker::Checker;
+use crate::visitor::Visitor;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum Value {
     String(String),
     Number(i32),
+    Float(f64),
     Bool(bool),
+    List(Vec<Value>),
+    // Maps are ordered by key so that they stay hashable and iterate deterministically.
+    Map(BTreeMap<String, Value>),
+    // The absence of a value, e.g. of a loop whose body never ran.
+    Null,
+    // A function made by an anonymous `fn`: its parameters and statements, and the name it calls
+    // itself by when it was bound with `let rec`.
+    Function(Rc<(Arguments, Statements)>, Option<String>),
 }
 
+// Rust-side equality is structural: variants must match and floats are compared by their bits,
+// so NaN equals itself and the relation is a true equivalence. Asa's `==` follows Value::equals.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            // a function is only equal to itself
+            (Value::Function(a, an), Value::Function(b, bn)) => Rc::ptr_eq(a, b) && an == bn,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+// Values are hashable so they can be used as keys (e.g. in maps or caches). Hash must agree
+// with PartialEq: equal values always hash the same.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::String(s) => s.hash(state),
+            Value::Number(n) => n.hash(state),
+            Value::Float(f) => f.to_bits().hash(state),
+            Value::Bool(b) => b.hash(state),
+            Value::List(l) => l.hash(state),
+            Value::Map(m) => m.hash(state),
+            Value::Null => {}
+            Value::Function(function, name) => {
+                Rc::as_ptr(function).hash(state);
+                name.hash(state);
+            }
+        }
+    }
+}
+
+// Values print the way they would be written in Asa source, except that a string on its own is
+// printed without quotes. Strings inside lists and maps are quoted.
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", s),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::List(list) => {
+                write!(f, "[")?;
+                for (i, element) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match element {
+                        Value::String(s) => write!(f, "{:?}", s)?,
+                        _ => write!(f, "{}", element)?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match value {
+                        Value::String(s) => write!(f, "{:?}: {:?}", key, s)?,
+                        _ => write!(f, "{:?}: {}", key, value)?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            Value::Null => write!(f, "null"),
+            Value::Function(_, Some(name)) => write!(f, "<fn {}>", name),
+            Value::Function(_, None) => write!(f, "<fn>"),
+        }
+    }
+}
+
+// Renders a value as JSON, for hosts passing results on to other programs. Lists become arrays
+// and maps objects. JSON has no functions or non-finite numbers, so those are written as null.
+pub fn value_to_json(v: &Value) -> String {
+    match v {
+        Value::String(s) => json_string(s),
+        Value::Number(n) => n.to_string(),
+        Value::Float(x) if x.is_finite() => format!("{:?}", x),
+        Value::Bool(b) => b.to_string(),
+        Value::List(list) => format!("[{}]", list.iter().map(value_to_json).collect::<Vec<_>>().join(",")),
+        Value::Map(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(key, value)| format!("{}:{}", json_string(key), value_to_json(value)))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Value::Float(_) | Value::Null | Value::Function(..) => "null".to_string(),
+    }
+}
+
+// A JSON string literal, with quotes, backslashes and control characters escaped.
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Value {
+        Value::Float(x)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(list: Vec<Value>) -> Value {
+        Value::List(list)
+    }
+}
+
+impl Value {
+    // Equality as seen by Asa's `==` and `!=`. Numbers compare by value whatever their
+    // representation, so `1 == 1.0` is true. Lists are equal when their elements are equal in
+    // order, and maps when they hold the same keys with equal values. Values of any other differing types are never equal.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Float(b)) | (Value::Float(b), Value::Number(a)) => *a as f64 == *b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::List(a), Value::List(b)) =>
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(va, vb)| va.equals(vb)),
+            (Value::Map(a), Value::Map(b)) =>
+                a.len() == b.len() &&
+                    a.iter().zip(b.iter()).all(|((ka, va), (kb, vb))| ka == kb && va.equals(vb)),
+            _ => self == other,
+        }
+    }
+
+    // The elements of a list, for Rust code that receives a value from the interpreter.
+    pub fn as_list(&self) -> Result<&[Value], AsaErrorKind> {
+        match self {
+            Value::List(list) => Ok(list),
+            _ => Err(AsaErrorKind::TypeMismatch("Expected a list".to_string())),
+        }
+    }
+
+    // The name of the value's type, as written in a type annotation. Integers and floats are both numbers.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "bool",
+            Value::Number(_) | Value::Float(_) => "number",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Null => "null",
+            Value::Function(..) => "function",
+        }
+    }
+
+    // Whether a value counts as true in a condition. false, 0, 0.0, "", empty lists and maps
+    // and null are false, every other value is true.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::List(l) => !l.is_empty(),
+            Value::Map(m) => !m.is_empty(),
+            Value::Null => false,
+            Value::Function(..) => true,
+        }
+    }
+}
+
+// The name of a function parameter. Parameters are parsed as expressions, so the identifier may be
+// wrapped in one, or be the first child of an annotated Parameter.
+fn parameter_name(param: &Node) -> Option<String> {
+    match param {
+        Node::Expression { children } | Node::Parameter { children } => children.first().and_then(parameter_name),
+        Node::Identifier { value } => Some(String::from_utf8_lossy(value).into_owned()),
+        _ => None,
+    }
+}
+
+// Checks that a value bound to `name` has the type its annotation declares.
+fn check_annotation(name: &str, annotation: &Node, value: &Value) -> Result<(), AsaErrorKind> {
+    let expected = match annotation {
+        Node::TypeAnnotation { value } => String::from_utf8_lossy(value).into_owned(),
+        _ => {
+            return Err(AsaErrorKind::Generic("Invalid type annotation".to_string()));
+        }
+    };
+    match expected.as_str() {
+        "bool" | "number" | "string" | "list" | "map" | "null" | "function" => {}
+        _ => {
+            return Err(AsaErrorKind::Generic(format!("Unknown type {}", expected)));
+        }
+    }
+    if value.type_name() == expected {
+        Ok(())
+    } else {
+        Err(
+            AsaErrorKind::TypeMismatch(
+                format!("{} is declared as a {} but was given a {}", name, expected, value.type_name())
+            )
+        )
+    }
+}
+
+// The value of an operand of && or ||, which must be a boolean.
+fn logical_operand(value: Value) -> Result<bool, AsaErrorKind> {
+    match value {
+        Value::Bool(value) => Ok(value),
+        _ => Err(AsaErrorKind::TypeMismatch("Logical operands must be booleans".to_string())),
+    }
+}
+
+// A pending step of Interpreter::exec_iterative.
+enum Work<'a> {
+    // Evaluate a node, leaving its value on the value stack.
+    Eval(&'a Node),
+    // Apply the operator of a node to the values of its operands.
+    Apply(&'a Node),
+    // Decide && or || from the value of its left operand.
+    Logical(&'a Node),
+    // Check the value of the right operand of && or ||.
+    LogicalRight,
+    // The Spanned node being evaluated is done.
+    LeaveSpan,
+}
+
+// The function value an operator function like add stands for: fn(a, b) { a + b }.
+fn operator_function(name: &str) -> Rc<(Arguments, Statements)> {
+    let parameter = |name: &[u8]| Node::Identifier { value: name.to_vec() };
+    let arguments = Node::FunctionArguments { children: vec![parameter(b"a"), parameter(b"b")] };
+    let operation = Node::MathExpression { name: name.as_bytes().to_vec(), children: vec![parameter(b"a"), parameter(b"b")] };
+    let statements = Node::FunctionStatements { children: vec![Node::Expression { children: vec![operation] }] };
+    Rc::new((arguments, statements))
+}
+
+// Whether exec_iterative evaluates this node itself rather than as a leaf.
+fn is_operator_node(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Spanned { .. } | Node::Expression { .. } | Node::MathExpression { .. } | Node::UnaryExpression { .. }
+    )
+}
+
+// Finds an assignment to something other than a variable, like `5 = x` or `foo() = 1`. Gives the
+// span of the statement when it has one.
+fn invalid_assignment(node: &Node) -> Option<Option<Span>> {
+    let is_invalid = |node: &Node| matches!(
+        node,
+        Node::Assignment { children } if !matches!(children.first(), Some(Node::Identifier { .. }))
+    );
+    match node {
+        _ if is_invalid(node) => Some(None),
+        Node::Spanned { span, children } if children.first().is_some_and(is_invalid) => Some(Some(*span)),
+        _ => node.children().iter().find_map(invalid_assignment),
+    }
+}
+
+// The next operand value of exec_iterative. Every step that needs one follows the steps leaving it,
+// so a missing value is a bug in the evaluator, reported rather than hidden.
+fn pop_value(values: &mut Vec<Value>) -> Result<Value, AsaErrorKind> {
+    values.pop().ok_or_else(|| AsaErrorKind::Generic("The iterative evaluator is missing an operand value".to_string()))
+}
+
+fn node_name(node: &Node) -> &[u8] {
+    match node {
+        Node::MathExpression { name, .. } | Node::UnaryExpression { name, .. } => name,
+        _ => &[],
+    }
+}
+
+// Converts the exact result of an integer operation back to a Number, or reports that it does not fit in one.
+fn checked_number(result: i64) -> Result<Value, AsaErrorKind> {
+    if result > i32::MAX as i64 {
+        Err(AsaErrorKind::NumberOverflow)
+    } else if result < i32::MIN as i64 {
+        Err(AsaErrorKind::NumberUnderflow)
+    } else {
+        Ok(Value::Number(result as i32))
+    }
+}
+
+// Applies an arithmetic operator to two floats. Integers are promoted to floats when mixed with them.
+fn float_math(name: &[u8], lhs: f64, rhs: f64) -> Result<Value, AsaErrorKind> {
+    match name {
+        b"add" => Ok(Value::Float(lhs + rhs)),
+        b"sub" => Ok(Value::Float(lhs - rhs)),
+        b"mul" => Ok(Value::Float(lhs * rhs)),
+        b"div" => Ok(Value::Float(lhs / rhs)),
+        _ => Err(AsaErrorKind::Generic("Unsupported operation in Math Expression".to_string())),
+    }
+}
+
+// Orders two numbers, or two strings, with one of the comparison operators lt, gt, le and ge.
+fn compare(name: &[u8], lhs: &Value, rhs: &Value) -> Result<Value, AsaErrorKind> {
+    let ordering = match (lhs, rhs) {
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+        (Value::Number(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Number(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+        _ => {
+            return Err(AsaErrorKind::TypeMismatch("Only two numbers or two strings can be compared".to_string()));
+        }
+    };
+    // a comparison with NaN is always false
+    let result = match ordering {
+        Some(ordering) => match name {
+            b"lt" => ordering.is_lt(),
+            b"gt" => ordering.is_gt(),
+            b"le" => ordering.is_le(),
+            b"ge" => ordering.is_ge(),
+            _ => return Err(AsaErrorKind::Generic("Unsupported comparison".to_string())),
+        },
+        None => false,
+    };
+    Ok(Value::Bool(result))
+}
+
+// Applies one of the bitwise operators bitand, bitor, bitxor, shl and shr to two integers. A shift moves the bits of the 32-bit value, by 0 to 31 places.
+fn bitwise(name: &[u8], lhs: &Value, rhs: &Value) -> Result<Value, AsaErrorKind> {
+    let (lhs, rhs) = match (lhs, rhs) {
+        (Value::Number(lhs), Value::Number(rhs)) => (*lhs, *rhs),
+        _ => {
+            return Err(AsaErrorKind::TypeMismatch("Bitwise operands must be integers".to_string()));
+        }
+    };
+    if (name == b"shl" || name == b"shr") && !(0..32).contains(&rhs) {
+        return Err(AsaErrorKind::Generic(format!("Cannot shift by {} bits", rhs)));
+    }
+    match name {
+        b"bitand" => Ok(Value::Number(lhs & rhs)),
+        b"bitor" => Ok(Value::Number(lhs | rhs)),
+        b"bitxor" => Ok(Value::Number(lhs ^ rhs)),
+        b"shl" => Ok(Value::Number(lhs << rhs)),
+        b"shr" => Ok(Value::Number(lhs >> rhs)),
+        _ => Err(AsaErrorKind::Generic("Unsupported bitwise operation".to_string())),
+    }
+}
+
+// Applies a binary operator other than && and || to two evaluated operands.
+fn binary_operation(name: &[u8], left_value: &Value, right_value: &Value) -> Result<Value, AsaErrorKind> {
+    // equality applies to any two values, see Value::equals for the rule
+    match name {
+        b"eq" => return Ok(Value::Bool(left_value.equals(right_value))),
+        b"ne" => return Ok(Value::Bool(!left_value.equals(right_value))),
+        b"lt" | b"gt" | b"le" | b"ge" => return compare(name, left_value, right_value),
+        b"bitand" | b"bitor" | b"bitxor" | b"shl" | b"shr" => return bitwise(name, left_value, right_value),
+        _ => {}
+    }
+
+    // perform the mathematical operation based on the operator
+    match (left_value, right_value) {
+        (Value::Number(lhs), Value::Number(rhs)) => {
+            // the operation is done in i64, where it cannot overflow, and the result is then checked against the i32 range
+            let (lhs, rhs) = (*lhs as i64, *rhs as i64);
+            let result = match name {
+                b"add" => lhs + rhs,
+                b"sub" => lhs - rhs,
+                b"mul" => lhs * rhs,
+                b"div" => {
+                    if rhs == 0 {
+                        return Err(AsaErrorKind::DivisionByZero);
+                    }
+                    lhs / rhs
+                }
+                // add more operators as needed, these are enough for now
+                _ => {
+                    return Err(
+                        AsaErrorKind::Generic(
+                            "Unsupported operation in Math Expression".to_string()
+                        )
+                    );
+                }
+                //anything else would fall under a wrong operation error ^
+            };
+            checked_number(result)
+        }
+        (Value::Float(lhs), Value::Float(rhs)) => float_math(name, *lhs, *rhs),
+        (Value::Number(lhs), Value::Float(rhs)) => float_math(name, *lhs as f64, *rhs),
+        (Value::Float(lhs), Value::Number(rhs)) => float_math(name, *lhs, *rhs as f64),
+        _ =>
+            Err(
+                AsaErrorKind::TypeMismatch(
+                    "MathExpression operands must be numbers".to_string()
+                )
+            ),
+        //if we got here, then the operands used for Math Expression were not number types ^
+    }
+}
+
+// The frame of a call: evaluated arguments bound to the parameters in order, checked against their annotations.
+fn bind_arguments(params: &[Node], args: Vec<Value>) -> Result<Frame, AsaErrorKind> {
+    if params.len() != args.len() {
+        return Err(AsaErrorKind::ArityMismatch { expected: params.len(), found: args.len() });
+    }
+    let mut frame = HashMap::new();
+    for (param, value) in params.iter().zip(args) {
+        let param_name = parameter_name(param).ok_or_else(||
+            AsaErrorKind::Generic("The parameter in the function's definition is not an identifier".to_string())
+        )?;
+        if let Node::Parameter { children } = param {
+            if let Some(annotation) = children.get(1) {
+                check_annotation(&param_name, annotation, &value)?;
+            }
+        }
+        frame.insert(param_name, value);
+    }
+    Ok(frame)
+}
+
+// An estimate of the bytes a value takes, its contents included.
+fn estimated_size(value: &Value) -> u64 {
+    let contents = match value {
+        Value::String(s) => s.len() as u64,
+        Value::List(list) => list.iter().map(estimated_size).sum(),
+        Value::Map(map) => map.iter().map(|(key, value)| key.len() as u64 + estimated_size(value)).sum(),
+        _ => 0,
+    };
+    std::mem::size_of::<Value>() as u64 + contents
+}
+
+// The estimated size of the values a frame holds.
+fn frame_size(frame: &Frame) -> u64 {
+    frame.values().map(estimated_size).sum()
+}
+
+// An estimate of the bytes the result of a builtin takes, made from its arguments before it runs.
+// repeat and range build values as large as their counts, zip adds a pair for each element, and
+// every other builtin builds a value no larger than its arguments.
+fn projected_size(name: &str, args: &[Value]) -> u64 {
+    let value_size = std::mem::size_of::<Value>() as u64;
+    let arguments_size: u64 = args.iter().map(estimated_size).sum();
+    match (name, args) {
+        ("repeat", [Value::String(s), Value::Number(n)]) => value_size.saturating_add((s.len() as u64).saturating_mul((*n).max(0) as u64)),
+        ("range", [Value::Number(start), Value::Number(end)]) => value_size * (1 + (*end as i64 - *start as i64).max(0) as u64),
+        ("zip", [Value::List(xs), Value::List(ys)]) => value_size * (1 + xs.len().min(ys.len()) as u64) + arguments_size,
+        _ => value_size + arguments_size,
+    }
+}
+
+// How an operator is written in source, for describing evaluation steps.
+fn operator_symbol(name: &[u8]) -> &'static str {
+    match name {
+        b"add" => "+",
+        b"sub" => "-",
+        b"mul" => "*",
+        b"div" => "/",
+        b"eq" => "==",
+        b"ne" => "!=",
+        b"lt" => "<",
+        b"gt" => ">",
+        b"le" => "<=",
+        b"ge" => ">=",
+        b"and" => "&&",
+        b"or" => "||",
+        b"bitand" => "&",
+        b"bitor" => "|",
+        b"bitxor" => "^",
+        b"shl" => "<<",
+        b"shr" => ">>",
+        _ => "?",
+    }
+}
+
+// A small prelude of functions written in Asa, for Interpreter::with_prelude.
+pub const PRELUDE: &str = r#"
+fn square(x) { x * x }
+fn double(x) { x + x }
+"#;
+
 type Frame = HashMap<String, Value>;
+// A function provided by the host program, called with the evaluated arguments.
+pub type NativeFunction = Box<dyn Fn(&[Value]) -> Result<Value, AsaErrorKind>>;
 type Arguments = Node;
 type Statements = Node;
 
-#[derive(Debug)]
 pub struct Interpreter {
     // Function Table:
-    // Key - Function name
+    // Key - Function name and number of parameters, functions of the same name are overloads told apart by their arity
     // Value - Vec<Node> arguments, statements
-    functions: HashMap<String, (Arguments, Statements)>,
+    // Definitions are shared so a call does not have to copy the function body.
+    functions: HashMap<(String, usize), Rc<(Arguments, Statements)>>,
+    // The doc comments of the defined functions that have one, with the same keys.
+    docs: HashMap<(String, usize), String>,
+    // Functions provided by the host, with the number of arguments they take. They are called before the functions defined in Asa.
+    natives: HashMap<String, (usize, NativeFunction)>,
     // Stack:
     // Each element in the stack is a function stack frame.
     // Crate a new stack frame on function entry.
@@ -26,6 +561,92 @@ pub struct Interpreter {
     // Key - Variable name
     // Value - Variable value
     stack: Vec<Frame>,
+    // Index of the frame the running function was called with. Blocks push frames above it, and
+    // names are looked up from the top of the stack down to it, so a function sees the variables
+    // of its enclosing blocks but not those of its caller. The global frame at index 0 is
+    // searched last, so every function sees the variables defined at the top of the program.
+    scope_base: usize,
+    // The constants defined so far, each with the index of the frame it belongs to. They end with their frame.
+    constants: HashSet<(usize, String)>,
+    // Where the input() builtin reads lines from. Defaults to stdin.
+    input: Box<dyn BufRead>,
+    // When set, builtins that reach outside the interpreter (like input()) are forbidden.
+    sandbox: bool,
+    // Number of nodes evaluated so far, and the optional budget it may not exceed.
+    steps: u64,
+    max_steps: Option<u64>,
+    // Estimated bytes of the strings, lists and maps built so far, and the optional budget they may not exceed.
+    memory: u64,
+    max_memory: Option<u64>,
+    // When set, conditions must be booleans instead of being coerced with Value::is_truthy.
+    strict: bool,
+    // When set, defining a variable again in the frame that already has it is an error instead of replacing it.
+    no_shadow: bool,
+    // The source range of the innermost spanned node whose evaluation failed last.
+    error_span: Option<Span>,
+    // When set, the names of the functions being called are kept so an error can report the chain of calls it happened in.
+    backtrace_enabled: bool,
+    call_stack: Vec<String>,
+    // The call chain of the last error, outermost call first.
+    backtrace: Option<Vec<String>>,
+    // The steps recorded while tracing, see exec_traced.
+    trace: Option<Vec<String>>,
+    // The text printed while exec_with_report captures it. Otherwise it goes to stdout.
+    output: Option<String>,
+    // When set, operator expressions are evaluated by exec_iterative instead of recursively.
+    iterative: bool,
+    // Set by a return statement until the enclosing function call is left, so the blocks in between stop early.
+    returning: bool,
+    // Set by a break statement until the innermost loop is left, like returning. A break is only
+    // allowed while the running function runs a loop, of which there are `loops`.
+    breaking: bool,
+    loops: usize,
+}
+
+// What exec_with_report found out about a run.
+#[derive(Debug, PartialEq)]
+pub struct ExecReport {
+    pub result: Result<Value, AsaErrorKind>,
+    // The number of nodes evaluated.
+    pub steps: u64,
+    // The errors the static check found, described, whether or not the run ran into them.
+    pub warnings: Vec<String>,
+    // Everything printed, by print and by the prompts of input.
+    pub output: String,
+}
+
+// The variables and functions of an interpreter at some point, which restore() returns it to.
+#[derive(Debug, Clone)]
+pub struct InterpreterSnapshot {
+    functions: HashMap<(String, usize), Rc<(Arguments, Statements)>>,
+    docs: HashMap<(String, usize), String>,
+    stack: Vec<Frame>,
+    scope_base: usize,
+    constants: HashSet<(usize, String)>,
+}
+
+impl std::fmt::Debug for Interpreter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interpreter")
+            .field("functions", &self.functions)
+            .field("stack", &self.stack)
+            .field("sandbox", &self.sandbox)
+            .field("strict", &self.strict)
+            .field("no_shadow", &self.no_shadow)
+            .field("error_span", &self.error_span)
+            .field("backtrace", &self.backtrace)
+            .field("steps", &self.steps)
+            .field("max_steps", &self.max_steps)
+            .field("memory", &self.memory)
+            .field("max_memory", &self.max_memory)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
 }
 
 impl Interpreter {
@@ -33,7 +654,29 @@ impl Interpreter {
         //changed this to make the Interpreter mutable
         let mut interpreter = Interpreter {
             functions: HashMap::new(),
+            docs: HashMap::new(),
+            natives: HashMap::new(),
             stack: Vec::new(),
+            scope_base: 0,
+            constants: HashSet::new(),
+            input: Box::new(std::io::BufReader::new(std::io::stdin())),
+            sandbox: false,
+            strict: false,
+            no_shadow: false,
+            error_span: None,
+            backtrace_enabled: false,
+            call_stack: Vec::new(),
+            backtrace: None,
+            trace: None,
+            output: None,
+            steps: 0,
+            max_steps: None,
+            memory: 0,
+            max_memory: None,
+            iterative: false,
+            returning: false,
+            breaking: false,
+            loops: 0,
         };
         // we initialize the stack with an empty global frame by pushing an empty HashMap onto it.
         interpreter.stack.push(HashMap::new());
@@ -41,21 +684,65 @@ impl Interpreter {
         interpreter
     }
 
+    // A new interpreter with the functions of a prelude source already defined, such as PRELUDE. Programs run with it can call them without defining them.
+    pub fn with_prelude(source: &str) -> Result<Interpreter, AsaErrorKind> {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_source(source)?;
+        Ok(interpreter)
+    }
+
     pub fn exec(&mut self, node: &Node) -> Result<Value, AsaErrorKind> {
+        if self.iterative && is_operator_node(node) {
+            return self.exec_iterative(node);
+        }
+        // A Spanned node evaluates the node it wraps. When that fails and no node inside it has claimed the error, the error is attributed to this node's span.
+        // It is handled here rather than in eval so a wrapper neither counts as a step nor costs a large stack frame.
+        if let Node::Spanned { span, children } = node {
+            self.error_span = None;
+            let result = children.first()
+                .ok_or_else(|| AsaErrorKind::Generic("Spanned must have a child".to_string()))
+                .and_then(|child| self.exec(child));
+            if result.is_err() && self.error_span.is_none() {
+                self.error_span = Some(*span);
+            }
+            return result;
+        }
+        self.eval(node)
+    }
+
+    fn eval(&mut self, node: &Node) -> Result<Value, AsaErrorKind> {
+        self.count_step()?;
         match node {
             Node::Program { children } => {
+                self.error_span = None;
+                self.backtrace = None;
+                // an assignment that could never run is reported before anything runs, even in a function that is never called
+                if let Some(span) = invalid_assignment(node) {
+                    self.error_span = span;
+                    return Err(AsaErrorKind::Generic("invalid assignment target".to_string()));
+                }
+                // functions are registered before anything runs, so code may call a function defined further down and functions may call each other
+                for n in children {
+                    if let Node::FunctionDefine { .. } = n {
+                        self.exec(n)?;
+                    }
+                }
                 let mut result = Ok(Value::Bool(true)); // a default value
                 for n in children {
                     match n {
-                        | Node::FunctionDefine { .. }
-                        | Node::Expression { .. }
-                        | Node::VariableDefine { .. }
-                        | Node::String { .. }
-                        | Node::Number { .. }
-                        | Node::Bool { .. } => {
+                        Node::FunctionDefine { .. } => {
+                            result = Ok(Value::Bool(true));
+                        }
+                        Node::Comment { .. } => {}
+                        // a statement or expression, possibly without its Expression wrapper (see Node::flatten)
+                        _ => {
                             result = self.exec(n);
                         }
-                        _ => unreachable!(),
+                    }
+                    // a return outside of any function ends the program
+                    if self.returning {
+                        self.returning = false;
+                        break;
                     }
                 }
                 result
@@ -72,52 +759,101 @@ impl Interpreter {
                         )
                     );
                 }
+                // && and || take booleans and only evaluate the right operand when the left one does not decide the result
+                if let b"and" | b"or" = name.as_slice() {
+                    let left_value = logical_operand(self.exec(&children[0])?)?;
+                    if left_value == (name.as_slice() == b"or") {
+                        return Ok(Value::Bool(left_value));
+                    }
+                    return logical_operand(self.exec(&children[1])?).map(Value::Bool);
+                }
+
                 // evaluate the left and right operands
                 let left_value = self.exec(&children[0])?;
                 let right_value = self.exec(&children[1])?;
-
-                // perform the mathematical operation based on the operator
-                match (left_value, right_value) {
-                    (Value::Number(lhs), Value::Number(rhs)) => {
-                        match name.as_slice() {
-                            b"add" => Ok(Value::Number(lhs + rhs)),
-                            b"sub" => Ok(Value::Number(lhs - rhs)),
-                            b"mul" => Ok(Value::Number(lhs * rhs)),
-                            b"div" => Ok(Value::Number(lhs / rhs)),
-                            // add more operators as needed, these are enough for now
-                            _ =>
-                                Err(
-                                    AsaErrorKind::Generic(
-                                        "Unsupported operation in Math Expression".to_string()
-                                    )
-                                ),
-                            //anything else would fall under a wrong operation error ^
-                        }
-                    }
-                    _ =>
-                        Err(
-                            AsaErrorKind::Generic(
-                                "MathExpression operands must be numbers".to_string()
-                            )
-                        ),
-                    //if we got here, then the operands used for Math Expression were not number types ^
+                self.apply_binary(name, &left_value, &right_value)
+            }
+            // Evaluates an operator applied to a single operand. "neg" negates a number, "pos" leaves it as it is.
+            Node::UnaryExpression { name, children } => {
+                if children.len() != 1 {
+                    return Err(
+                        AsaErrorKind::Generic(
+                            "UnaryExpression must have exactly one child".to_string()
+                        )
+                    );
                 }
+                let operand = self.exec(&children[0])?;
+                self.apply_unary(name, &operand)
             }
             // Defines a function that takes some arguments and executes a program based on those arguments. The code first checks if the function exists, and if it does, it creates a new scope in which to execute the function's statements (push a new Frame onto the interpreter stack). The code then executes each statement in the function's statements list and returns the result of the function's execution. You will have to correlate each passed value with the apprpriate variable in the called function. If the wrong number or an wrong type of variable is passed, return an error. On success, insert the return value of the function (if any) into the appropriate entry of the caller's stack.
             Node::FunctionCall { name, children } => {
                 //*DONE
                 // convert the function name from bytes to string
-                let function_name = String::from_utf8_lossy(&name);
-
-                // retrieve the function definition from the hashmap and clone the arguments and body
-                // using .as_ref to use a reference to the function name rather than getting ownership of the String
-                // I cloned because the trait `Borrow<Cow<'_, str>>` is not implemented for `String
-                let (func_args, func_body) = match
-                    self.functions.get(function_name.as_ref()).cloned()
-                {
-                    Some((args, body)) => (args, body),
+                let function_name = String::from_utf8_lossy(name);
+
+                // a variable holding a function value is called in preference to a named function
+                let mut recursive_name = None;
+                let function_value = match self.lookup(&function_name) {
+                    Some(Value::Function(function, name)) => {
+                        recursive_name = name.clone();
+                        Some(function.clone())
+                    }
+                    _ => None,
+                };
+                // a function provided by the host comes next, it is given the evaluated arguments
+                if function_value.is_none() {
+                    if let Some((arity, _)) = self.natives.get(function_name.as_ref()) {
+                        if *arity != children.len() {
+                            return Err(AsaErrorKind::ArityMismatch { expected: *arity, found: children.len() });
+                        }
+                        let mut args = Vec::new();
+                        for arg in children {
+                            if let Node::NamedArgument { .. } = arg {
+                                return Err(
+                                    AsaErrorKind::Generic(
+                                        format!("The native function {} does not take named arguments", function_name)
+                                    )
+                                );
+                            }
+                            args.push(self.exec(arg)?);
+                        }
+                        return match self.natives.get(function_name.as_ref()) {
+                            Some((_, native)) => native(&args),
+                            None => Err(AsaErrorKind::UndefinedFunction(function_name.to_string())),
+                        };
+                    }
+                }
+                // retrieve the definition taking as many arguments as the call passes. only the Rc is cloned, not the arguments and body
+                let key = (function_name.to_string(), children.len());
+                let function = match function_value.or_else(|| self.functions.get(&key).cloned()) {
+                    Some(function) => function,
                     None => {
-                        return Err(AsaErrorKind::UndefinedFunction);
+                        let mut overloads = self.overloads(&function_name);
+                        match overloads.len() {
+                            // builtins only run when no user function shadows them, with their arguments evaluated in the caller's frame
+                            0 if builtins::is_builtin(&function_name) => {
+                                if children.iter().any(|arg| matches!(arg, Node::NamedArgument { .. })) {
+                                    return Err(
+                                        AsaErrorKind::Generic(
+                                            format!("The builtin {} does not take named arguments", function_name)
+                                        )
+                                    );
+                                }
+                                let mut args = Vec::new();
+                                for arg in children {
+                                    args.push(self.exec(arg)?);
+                                }
+                                return self.call_builtin(&function_name, args);
+                            }
+                            0 => {
+                                return Err(AsaErrorKind::UndefinedFunction(function_name.to_string()));
+                            }
+                            // a function defined once is called anyway, so the error below names both argument counts
+                            1 => overloads.remove(0).1,
+                            _ => {
+                                return Err(AsaErrorKind::NoMatchingOverload(function_name.to_string()));
+                            }
+                        }
                     }
                 };
 
@@ -125,34 +861,90 @@ impl Interpreter {
                 let mut new_frame = HashMap::new();
 
                 // we match the function arguments with the provided children
+                let (func_args, func_body) = function.as_ref();
                 if let Node::FunctionArguments { children: params } = func_args {
                     if params.len() != children.len() {
-                        return Err(
-                            AsaErrorKind::Generic(
-                                format!(
-                                    "Expected a total of {} arguments, instead got only {} arguments",
-                                    params.len(),
-                                    children.len()
-                                )
-                            )
-                        );
+                        return Err(AsaErrorKind::ArityMismatch { expected: params.len(), found: children.len() });
                     }
 
-                    // iterate over the function parameters and passed arguments
-                    for (param, arg) in params.iter().zip(children.iter()) {
-                        if let Node::Identifier { value } = param {
-                            // convert parameter name from bytes to string
-                            let param_name = String::from_utf8_lossy(value).into_owned();
-                            // execute the argument expression and store its value in the frame
-                            let arg_value = self.exec(arg)?;
-                            new_frame.insert(param_name, arg_value);
-                        } else {
+                    let mut param_names = Vec::new();
+                    let mut annotations = Vec::new();
+                    for param in params {
+                        if let Node::Parameter { children } = param {
+                            if let Some(annotation) = children.get(1) {
+                                annotations.push((param_names.len(), annotation));
+                            }
+                        }
+                        match parameter_name(param) {
+                            Some(name) => param_names.push(name),
+                            None => {
+                                return Err(
+                                    AsaErrorKind::Generic(
+                                        "The parameter in the function's definition is not an identifier".to_string()
+                                    )
+                                );
+                            }
+                        }
+                    }
+
+                    // positional arguments come first and bind to the parameters in order
+                    let positional = children
+                        .iter()
+                        .take_while(|arg| !matches!(arg, Node::NamedArgument { .. }))
+                        .count();
+                    for (param_name, arg) in param_names.iter().zip(children[..positional].iter()) {
+                        // execute the argument expression and store its value in the frame
+                        let arg_value = self.exec(arg)?;
+                        new_frame.insert(param_name.clone(), arg_value);
+                    }
+
+                    // the named arguments after them bind to the parameter of the same name
+                    for arg in &children[positional..] {
+                        let (arg_name, arg) = match arg {
+                            Node::NamedArgument { children } => match children.as_slice() {
+                                [Node::Identifier { value }, arg] => (String::from_utf8_lossy(value).into_owned(), arg),
+                                _ => {
+                                    return Err(AsaErrorKind::Generic("Invalid named argument".to_string()));
+                                }
+                            },
+                            _ => {
+                                return Err(
+                                    AsaErrorKind::Generic(
+                                        "Positional arguments must come before named arguments".to_string()
+                                    )
+                                );
+                            }
+                        };
+                        if !param_names.contains(&arg_name) {
+                            return Err(
+                                AsaErrorKind::Generic(
+                                    format!("{} has no parameter named {}", function_name, arg_name)
+                                )
+                            );
+                        }
+                        if new_frame.contains_key(&arg_name) {
                             return Err(
                                 AsaErrorKind::Generic(
-                                    "The parameter in the function's definition is not an identifier".to_string()
+                                    format!("The argument {} was given more than once", arg_name)
                                 )
                             );
                         }
+                        let arg_value = self.exec(arg)?;
+                        new_frame.insert(arg_name, arg_value);
+                    }
+
+                    // every argument is bound, check those given to annotated parameters
+                    for (index, annotation) in annotations {
+                        let param_name = &param_names[index];
+                        if let Some(value) = new_frame.get(param_name) {
+                            check_annotation(param_name, annotation, value)?;
+                        }
+                    }
+
+                    // a function bound with let rec sees itself under its name, unless a parameter has that name
+                    if let Some(name) = recursive_name {
+                        let itself = Value::Function(function.clone(), Some(name.clone()));
+                        new_frame.entry(name).or_insert(itself);
                     }
                 } else {
                     //if we never got to match function arguments with the provided children
@@ -160,100 +952,164 @@ impl Interpreter {
                         AsaErrorKind::Generic("Function arguments were not provided".to_string())
                     );
                 }
-                // push the new frame onto the stack
-                self.stack.push(new_frame);
-                // then execute the function body
-                let result = self.exec(&func_body);
-                // pop the frame from the stack
-                self.stack.pop();
-
-                // return the result of the function execution
-                result
+                self.run_function(&function_name, func_body, new_frame)
             }
             // Defines a new function based on the elements in the children argument. The name of the function is retrieved from the node struct, the arguments are the first child, and the statements that define the function are the second child. A new key-value pair is then inserted into the functions table of the interprer. If the function was successfully defined, the code returns a Value object with a boolean value of true, otherwise an error is returned.
             Node::FunctionDefine { name, children } => {
-                //TODO: FIX THIS FUNCTION DEFINE?
+                if children.len() < 2 {
+                    return Err(
+                        AsaErrorKind::Generic(
+                            "FunctionDefine must have arguments and statements children".to_string()
+                        )
+                    );
+                }
                 // extract the function arguments and function statements
                 let function_arguments = match &children[0] {
-                    Node::FunctionArguments { children } =>
-                        Node::FunctionArguments { children: children.clone() },
+                    arguments @ Node::FunctionArguments { .. } => arguments.clone(),
                     _ => {
                         return Err(AsaErrorKind::Generic("Invalid function arguments".to_string()));
                     }
                 };
                 let function_statements = match &children[1] {
-                    Node::FunctionStatements { children } =>
-                        Node::FunctionStatements { children: children.clone() },
+                    statements @ Node::FunctionStatements { .. } => statements.clone(),
                     _ => {
                         return Err(
                             AsaErrorKind::Generic("Invalid function statements".to_string())
                         );
                     }
                 };
-                //convert the function name from a vector to a string
-                let function_name = String::from_utf8_lossy(name).to_string();
-                // clone the function name to so we can check if it exists in the functions table
-                let cloned_function_name = function_name.clone();
-
-                // insert the function into the functions map
-                self.functions.insert(function_name, (function_arguments, function_statements));
-                if self.functions.contains_key(&cloned_function_name) {
-                    Ok(Value::Bool(true))
-                } else {
-                    return Err(AsaErrorKind::UndefinedFunction);
+                //convert the function name from a vector to a string, it is stored along with the number of parameters
+                let key = (String::from_utf8_lossy(name).to_string(), function_arguments.children().len());
+                match children.get(2) {
+                    Some(Node::DocComment { value }) => {
+                        self.docs.insert(key.clone(), value.clone());
+                    }
+                    _ => {
+                        self.docs.remove(&key);
+                    }
+                }
+
+                // insert the function into the functions map, replacing an earlier definition with the same arity
+                self.functions.insert(key, Rc::new((function_arguments, function_statements)));
+                Ok(Value::Bool(true))
+            }
+            // An anonymous function evaluates to a function value. Like a named function it only sees its own parameters and locals when called.
+            Node::Lambda { children } => {
+                match children.as_slice() {
+                    [arguments @ Node::FunctionArguments { .. }, statements @ Node::FunctionStatements { .. }] =>
+                        Ok(Value::Function(Rc::new((arguments.clone(), statements.clone())), None)),
+                    _ => Err(AsaErrorKind::Generic("Invalid anonymous function".to_string())),
+                }
+            }
+            // Binds a function value that sees itself under the variable's name, so it can call itself recursively.
+            Node::RecursiveDefine { children } => {
+                let (variable_name, value_node) = match children.as_slice() {
+                    [Node::Identifier { value }, value_node] => (String::from_utf8_lossy(value).into_owned(), value_node),
+                    _ => {
+                        return Err(AsaErrorKind::Generic("Invalid let rec".to_string()));
+                    }
+                };
+                let function = match self.exec(value_node)? {
+                    Value::Function(function, _) => Value::Function(function, Some(variable_name.clone())),
+                    _ => {
+                        return Err(AsaErrorKind::TypeMismatch("let rec must bind a function".to_string()));
+                    }
+                };
+                self.trace(|| format!("bound {} to {}", variable_name, function));
+                self.check_can_define(&variable_name)?;
+                match self.stack.len().checked_sub(1) {
+                    Some(index) => {
+                        self.store(index, variable_name, function.clone())?;
+                        Ok(function)
+                    }
+                    None => Err(AsaErrorKind::Generic("There is no active frame available to define variable.".to_string())),
+                }
+            }
+            // Executes the statements of a block in a frame of its own, so the variables it defines end with it.
+            Node::Block { children } => {
+                self.stack.push(HashMap::new());
+                let result = self.exec_statements(children);
+                self.pop_frame();
+                // a block's value is that of its trailing expression, one ending in a statement has none
+                match (result, children.last()) {
+                    (Ok(_), Some(Node::Statement { .. })) if !self.returning && !self.breaking => Ok(Value::Null),
+                    (result, _) => result,
                 }
             }
+            // Executes the statements of a function body in the frame of the call.
+            Node::FunctionStatements { children } => self.exec_statements(children),
             // Calls the exec() method on the first element in the children argument, which recursively evaluates the AST of the program being executed and returns the resulting value or error message.
             Node::FunctionReturn { children } => {
                 //*DONE
                 //pretty simple, just call the exec() on the first element and then it'll recursively evaluate from thereon
-                self.exec(&children[0])
+                let value = children.first().ok_or_else(||
+                    AsaErrorKind::Generic("FunctionReturn must have a value to return".to_string())
+                )?;
+                let result = self.exec(value)?;
+                self.returning = true;
+                Ok(result)
+            }
+            // Runs the first block when the condition is true, otherwise the else branch if there is one. See condition() for what counts as true.
+            Node::If { children } => {
+                if children.len() < 2 || children.len() > 3 {
+                    return Err(AsaErrorKind::Generic("If must have two or three children".to_string()));
+                }
+                if self.condition(&children[0])? {
+                    self.exec(&children[1])
+                } else {
+                    match children.get(2) {
+                        Some(else_branch) => self.exec(else_branch),
+                        None => Ok(Value::Null),
+                    }
+                }
             }
-            // Retrieves the value of the identifier from the current frame on the stack. If the variable is defined in the current frame, the code returns its value. If the variable is not defined in the current frame, the code returns an error message.
+            // Retrieves the value of the identifier from the innermost frame of the running function that defines it. If no such frame defines the variable, the code returns an error message.
             Node::Identifier { value } => {
                 //*DONE
-                // we are converting the byte vector `value` to a `String` so we can find it in the hashmap
-                let identifier = String::from_utf8(value.clone()).map_err(|_|
+                // we borrow the byte vector `value` as a `str` so we can find it in the hashmap without copying it
+                let identifier = std::str::from_utf8(value).map_err(|_|
                     AsaErrorKind::Generic("Wrong sequence present in the identifier.".to_string())
                 )?;
 
-                // we check the current frame on the stack for the identifier.
-                // if there is a frame there, we retrieve the value associated with the identifier.
-                if let Some(frame) = self.stack.last() {
-                    if let Some(_val) = frame.get(&identifier) {
-                        let new_val = frame.get(&identifier).unwrap();
-                        println!(
-                            "Identifier '{}' was found with the value: {:?}",
-                            identifier,
-                            new_val
-                        );
-                        Ok(new_val.clone())
-                        // if the identifier is found in the frame, return its value. if it is not found, we return a `UndefinedFunction` error
-                    } else {
-                        println!("Identifier '{}' was not found.", identifier);
-                        Err(AsaErrorKind::UndefinedFunction)
-                    }
-                } else {
-                    // if there is no frame available, we give a `UndefinedFunction` error showing that no frame is there
-                    println!("No available frame for the '{}' identifier.", identifier);
-                    Err(AsaErrorKind::UndefinedFunction)
+                // we look the identifier up in the frames of the running function, innermost first, and clone only the value we return.
+                // if it is not found (or there is no frame), we return a `VariableNotDefined` error naming it
+                // an operator function like add is a function value, unless a variable has its name
+                match self.lookup(identifier) {
+                    Some(value) => Ok(value.clone()),
+                    None if builtins::is_operator_function(identifier) =>
+                        Ok(Value::Function(operator_function(identifier), None)),
+                    None => Err(AsaErrorKind::VariableNotDefined(identifier.to_string())),
                 }
             }
-            // Checks the type of the first element in the children argument and deciding what to do based on that type. If the type is a VariableDefine or FunctionReturn node, the code runs the run method on that node and returns the result.
+            // Checks the type of the first element in the children argument and deciding what to do based on that type. If the type is a VariableDefine, FunctionReturn or Expression node, the code runs the run method on that node and returns the result.
             Node::Statement { children } => {
-                //*DONE
-                //if the first child node matches either VariableDefine or FunctionReturn we execute it and return result
-                if let Node::VariableDefine { .. } | Node::FunctionReturn { .. } = &children[0] {
-                    self.exec(&children[0])
-                } else {
-                    return Err(AsaErrorKind::Generic("The expression is undefined".to_string()));
+                //if the first child node is a variable definition, a return or an expression statement we execute it and return result
+                match children.first() {
+                    Some(child) if matches!(
+                        child.unspanned(),
+                        Node::VariableDefine { .. }
+                        | Node::ConstDefine { .. }
+                        | Node::Destructure { .. }
+                        | Node::RecursiveDefine { .. }
+                        | Node::Assignment { .. }
+                        | Node::FunctionReturn { .. }
+                        | Node::Break { .. }
+                        | Node::Expression { .. }
+                    ) => self.exec(child),
+                    Some(child) =>
+                        Err(
+                            AsaErrorKind::Generic(
+                                format!("A {} node cannot be used as a statement", child.kind())
+                            )
+                        ),
+                    None => Err(AsaErrorKind::Generic("The statement is empty".to_string())),
                 }
             }
-            // Defines a new variable by assigning a name and a value to it. The name is retrieved from the first element of the children argument, and the value is retrieved by running the run method on the second element of the children argument. The key-value pair is then inserted into the last frame on the stack field of the current runtime object.
-            Node::VariableDefine { children } => {
+            // Defines a new variable by assigning a name and a value to it. A const is defined the same way and then cannot be assigned or defined again in its frame. The name is retrieved from the first element of the children argument, and the value is retrieved by running the run method on the second element of the children argument. The key-value pair is then inserted into the last frame on the stack field of the current runtime object.
+            Node::VariableDefine { children } | Node::ConstDefine { children } => {
                 //*DONE
-                // make sure that there are exactly two children: identifier and value.
-                if children.len() != 2 {
+                // make sure that there are two children: identifier and value, and maybe a type annotation.
+                if children.len() != 2 && children.len() != 3 {
                     return Err(
                         AsaErrorKind::Generic(
                             "VariableDefine must have exactly two children".to_string()
@@ -282,12 +1138,20 @@ impl Interpreter {
                     }
                 };
 
+                self.check_can_define(&variable_name)?;
                 // we then evaluate the value node to get the variable's value.
                 let variable_value = self.exec(value_node)?;
+                if let Some(annotation) = children.get(2) {
+                    check_annotation(&variable_name, annotation, &variable_value)?;
+                }
 
+                self.trace(|| format!("bound {} to {}", variable_name, variable_value));
+                if let Node::ConstDefine { .. } = node {
+                    self.constants.insert((self.stack.len() - 1, variable_name.clone()));
+                }
                 // insert the variable into the current frame on the stack.
-                if let Some(current_frame) = self.stack.last_mut() {
-                    current_frame.insert(variable_name, variable_value.clone());
+                if let Some(index) = self.stack.len().checked_sub(1) {
+                    self.store(index, variable_name, variable_value.clone())?;
                     Ok(variable_value)
                 } else {
                     Err(
@@ -297,9 +1161,227 @@ impl Interpreter {
                     )
                 }
             }
+            // Defines a variable for each element of a list, in order. The list must have exactly as many elements as there are names.
+            Node::Destructure { children } => {
+                let (value_node, names) = match children.split_last() {
+                    Some((value_node, names)) if !names.is_empty() => (value_node, names),
+                    _ => {
+                        return Err(AsaErrorKind::Generic("Destructure must have names and a value".to_string()));
+                    }
+                };
+                let mut variable_names = Vec::new();
+                for name in names {
+                    match name {
+                        Node::Identifier { value } => variable_names.push(String::from_utf8_lossy(value).into_owned()),
+                        _ => {
+                            return Err(AsaErrorKind::Generic("Only identifiers can be destructured into".to_string()));
+                        }
+                    }
+                }
+                for variable_name in &variable_names {
+                    self.check_can_define(variable_name)?;
+                }
+                let value = self.exec(value_node)?;
+                let elements = value.as_list()?;
+                if elements.len() != variable_names.len() {
+                    return Err(
+                        AsaErrorKind::Generic(
+                            format!(
+                                "Cannot destructure a list of {} elements into {} names",
+                                elements.len(),
+                                variable_names.len()
+                            )
+                        )
+                    );
+                }
+                for (variable_name, element) in variable_names.iter().zip(elements) {
+                    self.trace(|| format!("bound {} to {}", variable_name, element));
+                }
+                self.define_each(variable_names.into_iter().zip(elements.iter().cloned()))?;
+                Ok(value)
+            }
+            // Gives an existing variable a new value, in the innermost frame of the running function that defines it. Unlike let, assigning to a variable that was never defined is an error.
+            Node::Assignment { children } => {
+                let (variable_name, value_node) = match children.as_slice() {
+                    [Node::Identifier { value }, value_node] => (String::from_utf8_lossy(value).into_owned(), value_node),
+                    // only a variable can be assigned, not something like `5 = x` or `foo() = 1`
+                    [_, _] => {
+                        return Err(AsaErrorKind::Generic("invalid assignment target".to_string()));
+                    }
+                    _ => {
+                        return Err(AsaErrorKind::Generic("Invalid assignment".to_string()));
+                    }
+                };
+                let variable_value = self.exec(value_node)?;
+                let index = self.frame_of(&variable_name);
+                if let Some(index) = index {
+                    if self.constants.contains(&(index, variable_name.clone())) {
+                        return Err(AsaErrorKind::Generic("cannot reassign constant".to_string()));
+                    }
+                }
+                match index {
+                    Some(index) => {
+                        self.store(index, variable_name.clone(), variable_value.clone())?;
+                        self.trace(|| format!("bound {} to {}", variable_name, variable_value));
+                        Ok(variable_value)
+                    }
+                    None => Err(AsaErrorKind::VariableNotDefined(variable_name)),
+                }
+            }
+            // Builds a map from its entries. Each entry's key must evaluate to a string; a repeated key keeps the last value.
+            Node::Map { children } => {
+                let mut map = BTreeMap::new();
+                for entry in children {
+                    match entry {
+                        Node::MapEntry { children } if children.len() == 2 => {
+                            let key = match self.exec(&children[0])? {
+                                Value::String(key) => key,
+                                _ => {
+                                    return Err(
+                                        AsaErrorKind::TypeMismatch("Map keys must be strings".to_string())
+                                    );
+                                }
+                            };
+                            let value = self.exec(&children[1])?;
+                            map.insert(key, value);
+                        }
+                        _ => {
+                            return Err(AsaErrorKind::Generic("Invalid map entry".to_string()));
+                        }
+                    }
+                }
+                let map = Value::Map(map);
+                self.check_memory(&map)?;
+                Ok(map)
+            }
+            // Runs the body for as long as the condition is true. The loop's value is that of the last run of its body, or null if it never ran. A return or a break in the body leaves the loop.
+            Node::While { children } => {
+                if children.len() != 2 {
+                    return Err(AsaErrorKind::Generic("While must have exactly two children".to_string()));
+                }
+                self.in_loop(|interpreter| {
+                    let mut result = Value::Null;
+                    while interpreter.condition(&children[0])? {
+                        result = interpreter.exec(&children[1])?;
+                        if interpreter.returning || interpreter.breaking {
+                            break;
+                        }
+                    }
+                    Ok(result)
+                })
+            }
+            // Runs the body until a break or a return leaves it. The loop's value is that of the break.
+            Node::Loop { children } => {
+                let body = children.first().ok_or_else(||
+                    AsaErrorKind::Generic("Loop must have a body".to_string())
+                )?;
+                self.in_loop(|interpreter| {
+                    loop {
+                        let result = interpreter.exec(body)?;
+                        if interpreter.returning || interpreter.breaking {
+                            return Ok(result);
+                        }
+                    }
+                })
+            }
+            // Leaves the innermost loop of the running function, with the value if there is one or else null.
+            Node::Break { children } => {
+                if self.loops == 0 {
+                    return Err(AsaErrorKind::Generic("break outside of a loop".to_string()));
+                }
+                let result = match children.first() {
+                    Some(value) => self.exec(value)?,
+                    None => Value::Null,
+                };
+                self.breaking = true;
+                Ok(result)
+            }
+            // Evaluates the value and binds it to the variable in a frame of its own, then runs the body, for as long as the value is not null. The loop's value is like that of a while loop.
+            Node::WhileLet { children } => {
+                let (variable_name, value_node, body) = match children.as_slice() {
+                    [Node::Identifier { value }, value_node, body] =>
+                        (String::from_utf8_lossy(value).into_owned(), value_node, body),
+                    _ => {
+                        return Err(AsaErrorKind::Generic("Invalid while let".to_string()));
+                    }
+                };
+                self.in_loop(|interpreter| {
+                    let mut result = Value::Null;
+                    loop {
+                        let value = interpreter.exec(value_node)?;
+                        if value == Value::Null {
+                            break;
+                        }
+                        interpreter.trace(|| format!("bound {} to {}", variable_name, value));
+                        interpreter.push_frame(HashMap::from([(variable_name.clone(), value)]))?;
+                        let run = interpreter.exec(body);
+                        interpreter.pop_frame();
+                        result = run?;
+                        if interpreter.returning || interpreter.breaking {
+                            break;
+                        }
+                    }
+                    Ok(result)
+                })
+            }
+            // Evaluates the value, then the body of the first arm whose pattern equals it (see Value::equals). A `_` pattern matches anything. When no arm matches the result is a NoMatch error.
+            Node::Match { children } => {
+                let (scrutinee, arms) = match children.split_first() {
+                    Some(split) => split,
+                    None => {
+                        return Err(AsaErrorKind::Generic("Match must have a value to match on".to_string()));
+                    }
+                };
+                let value = self.exec(scrutinee)?;
+                for arm in arms {
+                    let (pattern, body) = match arm {
+                        Node::MatchArm { children } if children.len() == 2 => (&children[0], &children[1]),
+                        _ => {
+                            return Err(AsaErrorKind::Generic("Invalid match arm".to_string()));
+                        }
+                    };
+                    if matches!(pattern, Node::Wildcard) || self.exec(pattern)?.equals(&value) {
+                        return self.exec(body);
+                    }
+                }
+                Err(AsaErrorKind::NoMatch)
+            }
+            // Builds a list from its elements, evaluated in order.
+            Node::List { children } => {
+                let mut list = Vec::new();
+                for element in children {
+                    list.push(self.exec(element)?);
+                }
+                let list = Value::List(list);
+                self.check_memory(&list)?;
+                Ok(list)
+            }
+            // Looks up a key in a map. The first child evaluates to the map and the second to the key.
+            Node::Index { children } => {
+                if children.len() != 2 {
+                    return Err(
+                        AsaErrorKind::Generic("Index must have exactly two children".to_string())
+                    );
+                }
+                let target = self.exec(&children[0])?;
+                let key = self.exec(&children[1])?;
+                match (target, key) {
+                    (Value::Map(mut map), Value::String(key)) =>
+                        map.remove(&key).ok_or(AsaErrorKind::KeyNotFound(key)),
+                    (Value::Map(_), _) =>
+                        Err(AsaErrorKind::TypeMismatch("Map keys must be strings".to_string())),
+                    _ => Err(AsaErrorKind::TypeMismatch("Only maps can be indexed".to_string())),
+                }
+            }
             // Evaluate the child node using the exec() method.
-            Node::Expression { children } => { self.exec(&children[0]) } //*DONE
+            Node::Expression { children } => {
+                //*DONE
+                children.first()
+                    .ok_or_else(|| AsaErrorKind::Generic("Expression must have a child".to_string()))
+                    .and_then(|child| self.exec(child))
+            }
             Node::Number { value } => { Ok(Value::Number(*value)) } //*DONE
+            Node::Float { value } => { Ok(Value::Float(*value)) }
             Node::String { value } => { Ok(Value::String(value.clone())) } //*DONE
             Node::Bool { value } => { Ok(Value::Bool(*value)) } //*DONE
             // Return an error message.
@@ -310,11 +1392,705 @@ impl Interpreter {
         }
     }
 
+    // Checks a program without running it and returns every error found, see Checker for what is detected. Functions and global variables this interpreter already knows are taken into account.
+    pub fn check(&self, node: &Node) -> Vec<AsaErrorKind> {
+        let mut arities: HashMap<String, Vec<usize>> = HashMap::new();
+        for (name, arity) in self.functions.keys() {
+            arities.entry(name.clone()).or_default().push(*arity);
+        }
+        for (name, (arity, _)) in &self.natives {
+            arities.entry(name.clone()).or_default().push(*arity);
+        }
+        let globals = self.stack.first().map(|frame| frame.keys().cloned().collect()).unwrap_or_default();
+        let mut checker = Checker::new(arities, globals);
+        checker.define_functions(node);
+        checker.visit_node(node);
+        checker.errors
+    }
+
+    // The source range of the innermost expression that caused the last error, when the failing code came from parsed source.
+    pub fn error_span(&self) -> Option<Span> {
+        self.error_span
+    }
+
+    // Enables or disables backtraces. Keeping track of the calls costs a little on every call, so it is off by default.
+    pub fn set_backtrace(&mut self, enabled: bool) {
+        self.backtrace_enabled = enabled;
+    }
+
+    // The functions that were being called when the last error happened, outermost first. Only recorded while backtraces are enabled.
+    pub fn backtrace(&self) -> Option<&[String]> {
+        self.backtrace.as_deref()
+    }
+
+    // Evaluates a node like exec and also returns a description of each step taken, such as "evaluated 1 + 2 to 3" or "bound x to 3", in the order they happened.
+    pub fn exec_traced(&mut self, node: &Node) -> (Result<Value, AsaErrorKind>, Vec<String>) {
+        let outer = self.trace.replace(Vec::new());
+        let result = self.exec(node);
+        let steps = std::mem::replace(&mut self.trace, outer).unwrap_or_default();
+        (result, steps)
+    }
+
+    // Executes a node like exec and reports on the run: its result, the number of steps it took, the
+    // problems check() finds in the node beforehand and the text it printed, which is captured
+    // rather than written to stdout.
+    pub fn exec_with_report(&mut self, node: &Node) -> ExecReport {
+        let warnings = self.check(node).iter().map(|warning| warning.to_string()).collect();
+        let steps = self.steps;
+        let outer = self.output.replace(String::new());
+        let result = self.exec(node);
+        let output = std::mem::replace(&mut self.output, outer).unwrap_or_default();
+        ExecReport { result, steps: self.steps - steps, warnings, output }
+    }
+
+    // Executes each top-level item of a program and returns the result of every one, in order, rather than only the last like exec. An error does not stop the items after it, but a top-level return does. Comments have no result. Any other node gives the single result of exec.
+    pub fn exec_all(&mut self, node: &Node) -> Vec<Result<Value, AsaErrorKind>> {
+        let children = match node {
+            Node::Program { children } => children,
+            _ => {
+                return vec![self.exec(node)];
+            }
+        };
+        self.error_span = None;
+        self.backtrace = None;
+        // like exec, functions are registered first so statements can call functions defined below them
+        let mut results: Vec<Option<Result<Value, AsaErrorKind>>> = children
+            .iter()
+            .map(|n| match n {
+                Node::FunctionDefine { .. } => Some(self.exec(n)),
+                _ => None,
+            })
+            .collect();
+        for (n, result) in children.iter().zip(results.iter_mut()) {
+            if result.is_some() || matches!(n, Node::Comment { .. }) {
+                continue;
+            }
+            *result = Some(self.exec(n));
+            if self.returning {
+                self.returning = false;
+                break;
+            }
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    // Executes each top-level item of a program like exec_all, carrying on past the ones that fail. Returns the value of the last item that succeeded, if any did, and the errors of those that failed, in order.
+    pub fn exec_collect_errors(&mut self, node: &Node) -> (Option<Value>, Vec<AsaErrorKind>) {
+        let mut value = None;
+        let mut errors = vec![];
+        for result in self.exec_all(node) {
+            match result {
+                Ok(v) => value = Some(v),
+                Err(e) => errors.push(e),
+            }
+        }
+        (value, errors)
+    }
+
+    // Records a step when tracing. The description is only built when it is needed.
+    fn trace(&mut self, step: impl FnOnce() -> String) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(step());
+        }
+    }
+
+    // Every evaluated node counts as one step against the budget, if there is one.
+    fn count_step(&mut self) -> Result<(), AsaErrorKind> {
+        self.steps += 1;
+        match self.max_steps {
+            Some(max_steps) if self.steps > max_steps => Err(AsaErrorKind::StepLimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    // A string, list or map built by a literal or a builtin may not take the memory in use past the budget, if there is one. It only counts towards it once stored in a variable.
+    fn check_memory(&self, value: &Value) -> Result<(), AsaErrorKind> {
+        match self.max_memory {
+            Some(max_memory) if self.memory + estimated_size(value) > max_memory => Err(AsaErrorKind::MemoryLimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    // Stores a value in a variable of the frame at index, counting its estimated size in place of that of the value it replaces.
+    fn store(&mut self, index: usize, name: String, value: Value) -> Result<(), AsaErrorKind> {
+        if let Some(max_memory) = self.max_memory {
+            let replaced = self.stack[index].get(&name).map_or(0, estimated_size);
+            let memory = self.memory.saturating_sub(replaced) + estimated_size(&value);
+            if memory > max_memory {
+                return Err(AsaErrorKind::MemoryLimitExceeded);
+            }
+            self.memory = memory;
+        }
+        self.stack[index].insert(name, value);
+        Ok(())
+    }
+
+    // Defines each of several variables in the innermost frame, see store.
+    fn define_each(&mut self, bindings: impl Iterator<Item = (String, Value)>) -> Result<(), AsaErrorKind> {
+        let index = self.stack.len().checked_sub(1)
+            .ok_or_else(|| AsaErrorKind::Generic("There is no active frame available to define variable.".to_string()))?;
+        for (name, value) in bindings {
+            self.store(index, name, value)?;
+        }
+        Ok(())
+    }
+
+    // Pushes a frame, counting the estimated size of the values it already holds.
+    fn push_frame(&mut self, frame: Frame) -> Result<(), AsaErrorKind> {
+        if let Some(max_memory) = self.max_memory {
+            let memory = self.memory + frame_size(&frame);
+            if memory > max_memory {
+                return Err(AsaErrorKind::MemoryLimitExceeded);
+            }
+            self.memory = memory;
+        }
+        self.stack.push(frame);
+        Ok(())
+    }
+
+    // Applies a binary operator to evaluated operands, see binary_operation.
+    fn apply_binary(&mut self, name: &[u8], left_value: &Value, right_value: &Value) -> Result<Value, AsaErrorKind> {
+        let result = binary_operation(name, left_value, right_value);
+        if let Ok(value) = &result {
+            self.trace(|| format!("evaluated {} {} {} to {}", left_value, operator_symbol(name), right_value, value));
+        }
+        result
+    }
+
+    // Applies a unary operator to an evaluated operand. "neg" negates a number, "pos" leaves it as it is.
+    fn apply_unary(&mut self, name: &[u8], operand: &Value) -> Result<Value, AsaErrorKind> {
+        let result = match (name, operand) {
+            (b"neg", Value::Number(n)) => checked_number(-(*n as i64)),
+            (b"neg", Value::Float(f)) => Ok(Value::Float(-f)),
+            (b"neg", _) =>
+                Err(AsaErrorKind::TypeMismatch("Only numbers can be negated".to_string())),
+            (b"pos", Value::Number(_) | Value::Float(_)) => Ok(operand.clone()),
+            (b"pos", _) =>
+                Err(AsaErrorKind::TypeMismatch("Only numbers can take a unary plus".to_string())),
+            _ =>
+                Err(
+                    AsaErrorKind::Generic(
+                        "Unsupported operation in Unary Expression".to_string()
+                    )
+                ),
+        };
+        if let Ok(value) = &result {
+            let symbol = if name == b"pos" { "+" } else { "-" };
+            self.trace(|| format!("evaluated {}{} to {}", symbol, operand, value));
+        }
+        result
+    }
+
+    // Evaluates a tree of operators without recursing, keeping pending work and operand values on
+    // explicit stacks, so the depth of an expression is not limited by the native stack. It gives
+    // the same results, errors, spans, steps and trace as the recursive evaluator. Nodes other than
+    // operators, expressions and spans are evaluated with eval as leaves of the tree.
+    fn exec_iterative(&mut self, root: &Node) -> Result<Value, AsaErrorKind> {
+        let mut work = vec![Work::Eval(root)];
+        let mut values: Vec<Value> = Vec::new();
+        // the spans of the Spanned nodes being evaluated, innermost last
+        let mut spans: Vec<Span> = Vec::new();
+        while let Some(item) = work.pop() {
+            let result = match item {
+                Work::Eval(Node::Spanned { span, children }) => {
+                    self.error_span = None;
+                    match children.first() {
+                        Some(child) => {
+                            spans.push(*span);
+                            work.push(Work::LeaveSpan);
+                            work.push(Work::Eval(child));
+                            Ok(())
+                        }
+                        None => {
+                            self.error_span = Some(*span);
+                            Err(AsaErrorKind::Generic("Spanned must have a child".to_string()))
+                        }
+                    }
+                }
+                Work::Eval(node @ Node::Expression { children })
+                | Work::Eval(node @ Node::UnaryExpression { children, .. }) if children.len() == 1 => {
+                    self.count_step().map(|_| {
+                        if !matches!(node, Node::Expression { .. }) {
+                            work.push(Work::Apply(node));
+                        }
+                        work.push(Work::Eval(&children[0]));
+                    })
+                }
+                Work::Eval(node @ Node::MathExpression { name, children }) if children.len() == 2 => {
+                    self.count_step().map(|_| {
+                        if let b"and" | b"or" = name.as_slice() {
+                            work.push(Work::Logical(node));
+                        } else {
+                            work.push(Work::Apply(node));
+                            work.push(Work::Eval(&children[1]));
+                        }
+                        work.push(Work::Eval(&children[0]));
+                    })
+                }
+                // any other node, or a malformed operator that eval reports, is a leaf
+                Work::Eval(node) => self.eval(node).map(|value| values.push(value)),
+                Work::LeaveSpan => {
+                    spans.pop();
+                    Ok(())
+                }
+                Work::Apply(Node::MathExpression { name, .. }) => {
+                    pop_value(&mut values).and_then(|right_value| {
+                        let left_value = pop_value(&mut values)?;
+                        self.apply_binary(name, &left_value, &right_value).map(|value| values.push(value))
+                    })
+                }
+                Work::Apply(node) => {
+                    pop_value(&mut values)
+                        .and_then(|operand| self.apply_unary(node_name(node), &operand))
+                        .map(|value| values.push(value))
+                }
+                // the left operand of && or || is known, it decides the result or the right one is evaluated
+                Work::Logical(node) => {
+                    pop_value(&mut values).and_then(logical_operand).map(|left_value| {
+                        if left_value == (node_name(node) == b"or") {
+                            values.push(Value::Bool(left_value));
+                        } else {
+                            work.push(Work::LogicalRight);
+                            work.push(Work::Eval(&node.children()[1]));
+                        }
+                    })
+                }
+                Work::LogicalRight => {
+                    pop_value(&mut values).and_then(logical_operand).map(|value| values.push(Value::Bool(value)))
+                }
+            };
+            if let Err(error) = result {
+                // like the recursive evaluator, the innermost span around the failure claims it unless a node inside did
+                if self.error_span.is_none() {
+                    self.error_span = spans.last().copied();
+                }
+                return Err(error);
+            }
+        }
+        pop_value(&mut values)
+    }
+
+    // Executes statements in order. When a return is reached, here or in a nested block, its value is the result of the function and the remaining statements are skipped.
+    // An empty body has no value, it evaluates to null.
+    fn exec_statements(&mut self, children: &[Node]) -> Result<Value, AsaErrorKind> {
+        let mut result = Ok(Value::Null);
+        for n in children {
+            result = self.exec(n);
+            if result.is_err() || self.returning || self.breaking {
+                break;
+            }
+        }
+        result
+    }
+
+    // Runs a loop, during which a break may leave it. The break ends with the loop.
+    fn in_loop(&mut self, run: impl FnOnce(&mut Self) -> Result<Value, AsaErrorKind>) -> Result<Value, AsaErrorKind> {
+        self.loops += 1;
+        let result = run(self);
+        self.loops -= 1;
+        self.breaking = false;
+        result
+    }
+
+    // Evaluates the condition of a control-flow construct. Any value is coerced with Value::is_truthy, unless strict mode requires a boolean.
+    fn condition(&mut self, node: &Node) -> Result<bool, AsaErrorKind> {
+        match self.exec(node)? {
+            Value::Bool(b) => Ok(b),
+            _ if self.strict =>
+                Err(AsaErrorKind::TypeMismatch("A condition must be a boolean".to_string())),
+            value => Ok(value.is_truthy()),
+        }
+    }
+
+    // Enables or disables strict mode. In strict mode conditions must be booleans, a number or a string is a TypeMismatch error rather than being coerced.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    // The definitions of a function with their number of parameters, fewest parameters first.
+    fn overloads(&self, name: &str) -> Vec<(usize, Rc<(Arguments, Statements)>)> {
+        let mut overloads: Vec<_> = self.functions
+            .iter()
+            .filter(|((function_name, _), _)| function_name == name)
+            .map(|((_, arity), function)| (*arity, function.clone()))
+            .collect();
+        overloads.sort_by_key(|(arity, _)| *arity);
+        overloads
+    }
+
+    // The number of parameters a defined function declares. For an overloaded function it is that of the definition with the fewest.
+    pub fn function_arity(&self, name: &str) -> Option<usize> {
+        self.overloads(name).first().map(|(arity, _)| *arity)
+    }
+
+    // The names of a defined function's parameters, in order. For an overloaded function they are those of the definition with the fewest.
+    pub fn function_params(&self, name: &str) -> Option<Vec<String>> {
+        self.overloads(name)
+            .first()
+            .map(|(_, function)| function.0.children().iter().filter_map(parameter_name).collect())
+    }
+
+    // The doc comment written before the definition of a function, if it has one. For an overloaded function it is the first found from the definition with the fewest parameters.
+    pub fn function_doc(&self, name: &str) -> Option<&str> {
+        self.overloads(name)
+            .iter()
+            .find_map(|(arity, _)| self.docs.get(&(name.to_string(), *arity)))
+            .map(|doc| doc.as_str())
+    }
+
+    // Captures the variables and functions defined so far, so a caller can try running something and undo it with restore() if it fails.
+    pub fn snapshot(&self) -> InterpreterSnapshot {
+        InterpreterSnapshot {
+            functions: self.functions.clone(),
+            docs: self.docs.clone(),
+            stack: self.stack.clone(),
+            scope_base: self.scope_base,
+            constants: self.constants.clone(),
+        }
+    }
+
+    // Returns the variables and functions to those of a snapshot, dropping everything defined or changed since it was taken.
+    pub fn restore(&mut self, snapshot: InterpreterSnapshot) {
+        self.functions = snapshot.functions;
+        self.docs = snapshot.docs;
+        self.stack = snapshot.stack;
+        self.scope_base = snapshot.scope_base;
+        self.constants = snapshot.constants;
+        if self.max_memory.is_some() {
+            self.memory = self.stack.iter().map(frame_size).sum();
+        }
+        self.returning = false;
+        self.breaking = false;
+        self.loops = 0;
+    }
+
+    // Makes a Rust function callable from Asa code under the given name. It takes exactly `arity` arguments and is called in preference to a function of the same name defined in Asa. Defining it again replaces it.
+    pub fn define_native(&mut self, name: &str, arity: usize, f: NativeFunction) {
+        self.natives.insert(name.to_string(), (arity, f));
+    }
+
+    // Selects the evaluator for expressions. The iterative one gives the same results but keeps its work on the heap, so deeply nested expressions cannot overflow the native stack.
+    pub fn set_iterative(&mut self, iterative: bool) {
+        self.iterative = iterative;
+    }
+
+    // Limits the total number of nodes this interpreter may evaluate, so an untrusted program cannot run forever. Evaluating past the limit returns a StepLimitExceeded error.
+    pub fn set_max_steps(&mut self, limit: u64) {
+        self.max_steps = Some(limit);
+    }
+
+    // The number of nodes evaluated so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Limits the estimated bytes of the values held in variables, so an untrusted program cannot exhaust memory. A value counts while a variable holds it and stops counting when the variable is given another value or its frame ends. Storing or building a value past the limit returns a MemoryLimitExceeded error.
+    pub fn set_max_memory(&mut self, bytes: u64) {
+        self.max_memory = Some(bytes);
+        self.memory = self.stack.iter().map(frame_size).sum();
+    }
+
+    // The estimated bytes of the values variables hold now. It is only counted while there is a limit.
+    pub fn memory(&self) -> u64 {
+        self.memory
+    }
+
+    // Replaces the source the input() builtin reads from.
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = input;
+    }
+
+    // Enables or disables rejecting a let of a variable the current frame already defines. It is allowed by default, the new value replacing the old one.
+    pub fn set_no_shadow(&mut self, no_shadow: bool) {
+        self.no_shadow = no_shadow;
+    }
+
+    // Enables or disables sandbox mode. In sandbox mode builtins that reach outside the interpreter return a Forbidden error.
+    pub fn set_sandbox(&mut self, sandbox: bool) {
+        self.sandbox = sandbox;
+    }
+
+    // Pops the innermost frame, along with the constants defined in it and the memory its values took.
+    fn pop_frame(&mut self) {
+        if let Some(frame) = self.stack.pop() {
+            if self.max_memory.is_some() {
+                self.memory = self.memory.saturating_sub(frame_size(&frame));
+            }
+        }
+        let frames = self.stack.len();
+        self.constants.retain(|(frame, _)| *frame < frames);
+    }
+
+    // Defining a variable again in the frame where it is a constant is an error, as is defining it again at all with no_shadow set. An inner block may define its own variable of the same name.
+    fn check_can_define(&self, name: &str) -> Result<(), AsaErrorKind> {
+        let frame = self.stack.len().saturating_sub(1);
+        if self.constants.contains(&(frame, name.to_string())) {
+            return Err(AsaErrorKind::Generic("cannot reassign constant".to_string()));
+        }
+        if self.no_shadow && self.stack.last().is_some_and(|frame| frame.contains_key(name)) {
+            return Err(AsaErrorKind::Generic("variable already defined".to_string()));
+        }
+        Ok(())
+    }
+
+    // The index of the frame a variable is found in: the innermost frame of the running function that defines it, or else the global frame.
+    fn frame_of(&self, name: &str) -> Option<usize> {
+        (self.scope_base..self.stack.len())
+            .rev()
+            .chain(std::iter::once(0).filter(|_| self.scope_base > 0))
+            .find(|index| self.stack[*index].contains_key(name))
+    }
+
+    // The value of a variable, see frame_of for where it is looked for.
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.frame_of(name).and_then(|index| self.stack[index].get(name))
+    }
+
+    // Executes a function body in a new frame holding its arguments. The function sees nothing below that frame but the globals.
+    fn run_function(&mut self, function_name: &str, body: &Node, frame: Frame) -> Result<Value, AsaErrorKind> {
+        self.push_frame(frame)?;
+        let caller_base = std::mem::replace(&mut self.scope_base, self.stack.len() - 1);
+        // the loops of the caller cannot be left by a break in the function
+        let caller_loops = std::mem::replace(&mut self.loops, 0);
+        if self.backtrace_enabled {
+            self.call_stack.push(function_name.to_string());
+        }
+        // a return inside the body ends here
+        let result = self.exec(body);
+        self.returning = false;
+        if self.backtrace_enabled {
+            // the innermost call an error passes through records the chain, the calls it unwinds through keep it
+            if result.is_err() && self.backtrace.is_none() {
+                self.backtrace = Some(self.call_stack.clone());
+            }
+            self.call_stack.pop();
+        }
+        // pop the frame from the stack
+        self.pop_frame();
+        self.scope_base = caller_base;
+        self.loops = caller_loops;
+        result
+    }
+
+    // Calls a function value with arguments that are already evaluated, binding them to its parameters in order.
+    fn call_function_value(&mut self, function: &Rc<(Arguments, Statements)>, itself: Option<&String>, args: Vec<Value>) -> Result<Value, AsaErrorKind> {
+        let (func_args, func_body) = function.as_ref();
+        let mut frame = bind_arguments(func_args.children(), args)?;
+        if let Some(name) = itself {
+            frame.entry(name.clone()).or_insert_with(|| Value::Function(function.clone(), Some(name.clone())));
+        }
+        let function_name = itself.map_or("<anonymous>", |name| name.as_str());
+        self.run_function(function_name, func_body, frame)
+    }
+
+    // filter(xs, pred) is the list of the elements of xs for which the function pred returns true, and count(xs, pred) is how many there are.
+    fn filter(&mut self, name: &str, args: Vec<Value>) -> Result<Value, AsaErrorKind> {
+        let (items, function, itself) = match args.as_slice() {
+            [Value::List(items), Value::Function(function, itself)] if function.0.children().len() == 1 =>
+                (items.clone(), function.clone(), itself.clone()),
+            _ => {
+                return Err(
+                    AsaErrorKind::TypeMismatch(format!("{} expects a list and a function of one argument", name))
+                );
+            }
+        };
+        let mut kept = vec![];
+        for item in items {
+            match self.call_function_value(&function, itself.as_ref(), vec![item.clone()])? {
+                Value::Bool(true) => kept.push(item),
+                Value::Bool(false) => {}
+                other => {
+                    return Err(
+                        AsaErrorKind::TypeMismatch(
+                            format!("The predicate of {} must return a bool, not a {}", name, other.type_name())
+                        )
+                    );
+                }
+            }
+        }
+        if name == "count" {
+            Ok(Value::Number(kept.len() as i32))
+        } else {
+            Ok(Value::List(kept))
+        }
+    }
+
+    // reduce(xs, f, init) combines the elements of xs in order with the function f of two arguments, starting from init: f(f(init, x1), x2) and so on.
+    fn reduce(&mut self, args: Vec<Value>) -> Result<Value, AsaErrorKind> {
+        let (items, function, itself, mut accumulator) = match args.as_slice() {
+            [Value::List(items), Value::Function(function, itself), init] if function.0.children().len() == 2 =>
+                (items.clone(), function.clone(), itself.clone(), init.clone()),
+            _ => {
+                return Err(
+                    AsaErrorKind::TypeMismatch(
+                        "reduce expects a list, a function of two arguments and an initial value".to_string()
+                    )
+                );
+            }
+        };
+        for item in items {
+            accumulator = self.call_function_value(&function, itself.as_ref(), vec![accumulator, item])?;
+        }
+        Ok(accumulator)
+    }
+
+    // Calls a builtin with evaluated arguments. Neither the size its result is projected to have
+    // before it runs nor the size of what it returns may take the memory in use past the budget.
+    fn call_builtin(&mut self, function_name: &str, args: Vec<Value>) -> Result<Value, AsaErrorKind> {
+        if let Some(max_memory) = self.max_memory {
+            if self.memory.saturating_add(projected_size(function_name, &args)) > max_memory {
+                return Err(AsaErrorKind::MemoryLimitExceeded);
+            }
+        }
+        let result = match function_name {
+            "input" => self.read_input(&args),
+            "print" => self.print(&args),
+            "getenv" => self.getenv(&args),
+            "is_defined" => self.is_defined(&args),
+            "filter" | "count" => self.filter(function_name, args),
+            "reduce" => self.reduce(args),
+            name if builtins::is_operator_function(name) =>
+                self.call_function_value(&operator_function(name), None, args),
+            _ => builtins::call_builtin(function_name, &args),
+        }?;
+        self.check_memory(&result)?;
+        Ok(result)
+    }
+
+    // is_defined(name) tells whether a variable of that name can be read where it is called.
+    fn is_defined(&self, args: &[Value]) -> Result<Value, AsaErrorKind> {
+        match args {
+            [Value::String(name)] => Ok(Value::Bool(self.lookup(name).is_some())),
+            _ => Err(AsaErrorKind::TypeMismatch("is_defined expects a variable name".to_string())),
+        }
+    }
+
+    // input() or input(prompt) reads one line from the input source, without its line ending. The prompt, if any, is printed first.
+    fn read_input(&mut self, args: &[Value]) -> Result<Value, AsaErrorKind> {
+        if self.sandbox {
+            return Err(AsaErrorKind::Forbidden("input".to_string()));
+        }
+        match args {
+            [] => {}
+            [Value::String(prompt)] => self.write_output(prompt)?,
+            _ => {
+                return Err(AsaErrorKind::TypeMismatch("input expects an optional string prompt".to_string()));
+            }
+        }
+        let mut line = String::new();
+        self.input.read_line(&mut line).map_err(|e| AsaErrorKind::Generic(e.to_string()))?;
+        let trimmed = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(trimmed);
+        Ok(Value::String(line))
+    }
+
+    // print(x, ...) writes its arguments separated by spaces, and then a line ending. Its value is null.
+    fn print(&mut self, args: &[Value]) -> Result<Value, AsaErrorKind> {
+        let line = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(" ");
+        self.write_output(&format!("{}\n", line))?;
+        Ok(Value::Null)
+    }
+
+    // Writes text to stdout, or to the captured output while there is one.
+    fn write_output(&mut self, text: &str) -> Result<(), AsaErrorKind> {
+        match self.output.as_mut() {
+            Some(output) => output.push_str(text),
+            None => {
+                print!("{}", text);
+                std::io::stdout().flush().map_err(|e| AsaErrorKind::Generic(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // getenv(name) is the value of an environment variable of the host, or null when it is not set.
+    fn getenv(&self, args: &[Value]) -> Result<Value, AsaErrorKind> {
+        if self.sandbox {
+            return Err(AsaErrorKind::Forbidden("getenv".to_string()));
+        }
+        match args {
+            [Value::String(name)] => Ok(std::env::var(name).map(Value::String).unwrap_or(Value::Null)),
+            _ => Err(AsaErrorKind::TypeMismatch("getenv expects a variable name".to_string())),
+        }
+    }
+
+    // Parses a source and adds its functions to this interpreter without calling main, so a program can be split across several sources. Top-level statements are run in order. Defining a function that an earlier source (or this one) already defined with the same number of parameters is an error.
+    pub fn load_source(&mut self, source: &str) -> Result<(), AsaErrorKind> {
+        self.load(source).map(|_| ())
+    }
+
+    // Runs a whole program: its top-level statements, then main if it defines one. The result is main's value, or that of the top-level statements for a program without main.
+    pub fn run_source(&mut self, source: &str) -> Result<Value, AsaErrorKind> {
+        let value = self.load(source)?;
+        if !self.overloads("main").is_empty() {
+            self.start_main(vec![])
+        } else {
+            Ok(value)
+        }
+    }
+
+    // The work of load_source, returning the value of the top-level statements.
+    fn load(&mut self, source: &str) -> Result<Value, AsaErrorKind> {
+        let tokens = lex(source);
+        validate(&tokens)?;
+        let unexpected = |token: &Token| AsaErrorKind::ParseError(format!(
+            "Unexpected {:?} token at line {}, column {}", token.kind, token.start_line, token.start_col
+        ));
+        let (result, furthest) = with_furthest_failure(&tokens, || program(tokens.clone()));
+        // the parser stops where it cannot make sense of the rest, report the token it got furthest to in it
+        let tree = match result {
+            Ok((rest, _)) if !rest.is_done() => return Err(unexpected(furthest.unwrap_or(&rest.tokens[0]))),
+            Ok((_, tree)) => tree,
+            Err(_) => {
+                return Err(furthest.map_or_else(
+                    || AsaErrorKind::ParseError("Unexpected end of input".to_string()),
+                    unexpected,
+                ));
+            }
+        };
+        let tree = tree.flatten();
+        if let Node::Program { children } = &tree {
+            let mut defined = HashSet::new();
+            for n in children.iter() {
+                if let Node::FunctionDefine { name, children } = n {
+                    // overloads with different numbers of parameters are not duplicates
+                    let arity = children.first().map_or(0, |params| params.children().len());
+                    let key = (String::from_utf8_lossy(name).to_string(), arity);
+                    if self.functions.contains_key(&key) || !defined.insert(key.clone()) {
+                        return Err(AsaErrorKind::DuplicateFunction(key.0));
+                    }
+                }
+            }
+        }
+        self.exec(&tree)
+    }
+
+    // Calls a defined function with values for its arguments, for Rust code that has values rather than nodes. The definition taking that many arguments is called.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, AsaErrorKind> {
+        let function = match self.functions.get(&(name.to_string(), args.len())) {
+            Some(function) => function.clone(),
+            None => {
+                return Err(match self.overloads(name).as_slice() {
+                    [] => AsaErrorKind::UndefinedFunction(name.to_string()),
+                    [(arity, _)] => AsaErrorKind::ArityMismatch { expected: *arity, found: args.len() },
+                    _ => AsaErrorKind::NoMatchingOverload(name.to_string()),
+                });
+            }
+        };
+        let (func_args, func_body) = function.as_ref();
+        let frame = bind_arguments(func_args.children(), args)?;
+        self.error_span = None;
+        self.backtrace = None;
+        self.run_function(name, func_body, frame)
+    }
+
     pub fn start_main(&mut self, arguments: Vec<Node>) -> Result<Value, AsaErrorKind> {
         // This node is equivalent to the following Asa program source code:
         // "main()"
-        // It calls the main function with a FunctionArguments node as input.
+        // It calls the main function with the given argument expressions.
         let start_main = Node::FunctionCall { name: "main".into(), children: arguments };
+        self.error_span = None;
+        self.backtrace = None;
         // Call the main function by running this code through the interpreter.
         self.exec(&start_main)
     }
@@ -1,251 +1,538 @@
-use nom::*;
-
-use core::iter::*;
-
-#[derive(Debug, PartialEq, Clone)]
-pub struct Token {
-  pub kind: TokenKind,
-  pub lexeme: Vec<u8>,
-  pub start_line: u32,
-  pub end_line: u32,
-  pub start_col: u32,
-  pub end_col: u32,
-}
-
-impl Token {
-  pub fn new() -> Token {
-    Token{
-      kind: TokenKind::Other, 
-      lexeme: vec![],
-      start_line: 0,
-      end_line: 0,
-      start_col: 0,
-      end_col: 0,
-    }
-  }
-
-  pub fn get_kind(&self) -> TokenKind {
-    self.kind
-  }
-
-  pub fn set_kind(&mut self, new_kind: TokenKind) {
-    self.kind = new_kind;
-  }
-
-}
-
-
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum TokenKind {
-  // Keywords
-  True,
-  False,
-  Fn,
-  Return,
-  Let,
-  //------
-  Alpha,
-  Digit,
-  LeftParen,
-  RightParen,
-  LeftCurly,
-  RightCurly,
-  Equal,
-  Plus,
-  Dash,
-  Quote,
-  WhiteSpace,
-  Semicolon,
-  Comma,
-  Slash,
-  Other,
-  EOF,
-}
-
-#[derive(Debug, PartialEq, Clone)]
-pub struct Tokens {
-    pub tokens: Vec<Token>,
-}
-
-impl Tokens {
-    pub fn new() -> Tokens {
-        Tokens { tokens: vec![] }
-    }
-
-    pub fn from(tokens: Vec<Token>) -> Tokens {
-        Tokens { tokens }
-    }
-
-    pub fn push(&mut self, token: Token) {
-        self.tokens.push(token);
-    }
-
-    pub fn len(&self) -> usize {
-      self.tokens.len()
-    }
-
-    pub fn is_done(&self) -> bool {
-        if !self.is_empty() {
-            match &self.tokens[0].kind {
-                TokenKind::EOF => true,
-                _ => false,
-            }
-        } else {
-            true
-        }
-    }
-
-    pub fn is_empty(&self) -> bool {
-      self.tokens.is_empty()
-    }
-
-}
-
-impl InputLength for Tokens {
-    fn input_len(&self) -> usize {
-        self.tokens.len()
-    }
-}
-
-impl InputTake for Tokens{
-    fn take(&self, count: usize) -> Self {
-        Tokens {
-            tokens: self.tokens.iter().take(count).cloned().collect(),
-        }
-    }
-    fn take_split(&self, count: usize) -> (Self, Self) {
-        let (left, right) = self.tokens.split_at(count);
-        (Tokens { tokens: left.to_vec() }, Tokens { tokens: right.to_vec() })
-    }
-}
-
-pub fn split_tokens(input: Tokens) -> IResult<Tokens, Token> {
-  if input.is_empty() {
-      Err(Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Eof)))
-  } else {
-      let first = input.tokens[0].clone();
-      let rest = input.tokens.into_iter().skip(1).collect();
-      Ok((Tokens::from(rest), first))
-  }
-}
-
-pub fn check_token(pred: &dyn Fn(&Token) -> bool) -> impl Fn(Tokens) -> IResult<Tokens, Token> + '_ {
-  move |input: Tokens| {
-      let parse_res = split_tokens(input.clone())?;
-      if pred(&(parse_res.1)) {
-          Ok(parse_res)
-      } else {
-          combinator::fail(input)
-      }
-  }
-}
-
-pub fn lex(input: &str) -> Tokens {
-  let mut tokens = Tokens::new();
-  let list = input.as_bytes();
-  let mut i = 0;
-  let mut line = 1;
-  let mut col = 1;
-  let mut diff = 0;
-  while i < list.len() {
-    let c = list[i];
-    let mut kind = match c {
-        48..=57 => TokenKind::Digit,
-        65..=90 | 97..=122 => TokenKind::Alpha,
-        32 | 10 | 9=> TokenKind::WhiteSpace,
-        61 => TokenKind::Equal,
-        59 => TokenKind::Semicolon,
-        123 => TokenKind::LeftCurly,
-        125 => TokenKind::RightCurly,
-        40 => TokenKind::LeftParen,
-        41 => TokenKind::RightParen,
-        43 => TokenKind::Plus,
-        45 => TokenKind::Dash,
-        44 => TokenKind::Comma,
-        34 => TokenKind::Quote,
-        _x => TokenKind::Other,
-    };
-    //check if fn
-    if c == b'f' {
-        if i + 1 < list.len(){
-          if list[i + 1] == b'n' {
-            kind = TokenKind::Fn;
-            i += 1;
-            diff = 1;
-          }
-        }
-    }
-    //check if true
-    if c == b't' {
-      if i + 3 < list.len(){
-        if list[i + 1] == b'r' && list[i + 2] == b'u' && list[i + 3] == b'e' {
-          kind = TokenKind::True;
-          i += 3;
-          diff = 3;
-        }
-      }
-    }
-    //check if false
-    if c == b'f' {
-      if i + 4 < list.len(){
-        if list[i + 1] == b'a' && list[i + 2] == b'l' && list[i + 3] == b's' && list[i + 4] == b'e' {
-          kind = TokenKind::False;
-          i += 4;
-          diff = 4;
-        }
-      }
-    }
-    //check if let
-    if c == b'l' { 
-      if i + 2 < list.len(){
-        if list[i + 1] == b'e' && list[i + 2] == b't' {
-          kind = TokenKind::Let;
-          i += 2;
-          diff = 2;
-        }
-      }
-    }
-    //check if return
-    if c == b'r' {
-      if i + 5 < list.len(){
-        if list[i + 1] == b'e' && list[i + 2] == b't' && list[i + 3] == b'u' && list[i + 4] == b'r' && list[i + 5] == b'n' {
-          kind = TokenKind::Return;
-          i += 5;
-          diff = 5;
-        }
-      }
-    }
-
-    //create token struct
-    let token = Token {
-        kind,
-        lexeme: vec![c],
-        start_col: col,
-        end_col: col + diff,
-        start_line: line,
-        end_line: line,
-    };
-    i +=1;
-    diff = 0;
-    tokens.push(token.clone());
-    col +=1;
-    if c == 10{
-      line +=1;
-      col = 1;
-    }
-  }
-
-  let token = Token {
-    kind: TokenKind::EOF,
-    lexeme: vec![],
-    start_col: col,
-    end_col: col ,
-    start_line: line,
-    end_line: line,
-    };
-  tokens.push(token);
-
-  
-  let filtered_tokens: Vec<Token> = tokens.tokens.iter().filter(|tkn| tkn.kind != TokenKind::WhiteSpace).cloned().collect();
-  Tokens::from(filtered_tokens)
-}
+use nom::*;
+
+use core::iter::*;
+use std::cell::Cell;
+use crate::error::AsaErrorKind;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token {
+  pub kind: TokenKind,
+  // the source text of the token, except that a raw identifier's is its name without r#
+  pub lexeme: Vec<u8>,
+  pub start_line: u32,
+  pub end_line: u32,
+  pub start_col: u32,
+  pub end_col: u32,
+  // byte offsets of the lexeme in the source, the end is exclusive
+  pub start_offset: usize,
+  pub end_offset: usize,
+}
+
+impl Default for Token {
+  fn default() -> Self {
+    Token::new()
+  }
+}
+
+impl Token {
+  pub fn new() -> Token {
+    Token{
+      kind: TokenKind::Other, 
+      lexeme: vec![],
+      start_line: 0,
+      end_line: 0,
+      start_col: 0,
+      end_col: 0,
+      start_offset: 0,
+      end_offset: 0,
+    }
+  }
+
+  pub fn get_kind(&self) -> TokenKind {
+    self.kind
+  }
+
+  pub fn set_kind(&mut self, new_kind: TokenKind) {
+    self.kind = new_kind;
+  }
+
+}
+
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TokenKind {
+  // Keywords
+  True,
+  False,
+  Fn,
+  Return,
+  Let,
+  If,
+  Else,
+  While,
+  Match,
+  Rec,
+  Const,
+  Loop,
+  Break,
+  //------
+  Alpha,
+  Digit,
+  LeftParen,
+  RightParen,
+  LeftCurly,
+  RightCurly,
+  Equal,
+  Plus,
+  Dash,
+  Quote,
+  WhiteSpace,
+  Semicolon,
+  Comma,
+  Slash,
+  Star,
+  Colon,
+  LeftBracket,
+  RightBracket,
+  TripleQuote,
+  // r" opening a raw string, which a Quote closes.
+  RawQuote,
+  Dot,
+  Underscore,
+  EqualEqual,
+  NotEqual,
+  RawIdentifier,
+  Less,
+  Greater,
+  LessEqual,
+  GreaterEqual,
+  AndAnd,
+  OrOr,
+  Ampersand,
+  Pipe,
+  PipeForward,
+  Caret,
+  ShiftLeft,
+  ShiftRight,
+  FatArrow,
+  PlusEqual,
+  Other,
+  EOF,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tokens {
+    pub tokens: Vec<Token>,
+}
+
+impl Default for Tokens {
+    fn default() -> Self {
+        Tokens::new()
+    }
+}
+
+impl Tokens {
+    pub fn new() -> Tokens {
+        Tokens { tokens: vec![] }
+    }
+
+    pub fn from(tokens: Vec<Token>) -> Tokens {
+        Tokens { tokens }
+    }
+
+    pub fn push(&mut self, token: Token) {
+        self.tokens.push(token);
+    }
+
+    pub fn len(&self) -> usize {
+      self.tokens.len()
+    }
+
+    pub fn is_done(&self) -> bool {
+        if !self.is_empty() {
+            matches!(&self.tokens[0].kind, TokenKind::EOF)
+        } else {
+            true
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+      self.tokens.is_empty()
+    }
+
+}
+
+impl InputLength for Tokens {
+    fn input_len(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+impl InputTake for Tokens{
+    fn take(&self, count: usize) -> Self {
+        Tokens {
+            tokens: self.tokens.iter().take(count).cloned().collect(),
+        }
+    }
+    fn take_split(&self, count: usize) -> (Self, Self) {
+        let (left, right) = self.tokens.split_at(count);
+        (Tokens { tokens: left.to_vec() }, Tokens { tokens: right.to_vec() })
+    }
+}
+
+pub fn split_tokens(input: Tokens) -> IResult<Tokens, Token> {
+  if input.is_empty() {
+      Err(Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Eof)))
+  } else {
+      let first = input.tokens[0].clone();
+      let rest = input.tokens.into_iter().skip(1).collect();
+      Ok((Tokens::from(rest), first))
+  }
+}
+
+thread_local! {
+  // the fewest tokens left when a token check failed, which marks how far parsing got
+  static FEWEST_LEFT: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+pub fn check_token(pred: &dyn Fn(&Token) -> bool) -> impl Fn(Tokens) -> IResult<Tokens, Token> + '_ {
+  move |input: Tokens| {
+      let parse_res = split_tokens(input.clone())?;
+      if pred(&(parse_res.1)) {
+          Ok(parse_res)
+      } else {
+          FEWEST_LEFT.with(|left| left.set(left.get().min(input.tokens.len())));
+          combinator::fail(input)
+      }
+  }
+}
+
+// Runs a parse over `tokens` and returns its result with the token it got furthest to before a
+// check failed, which is where a parse that failed went wrong.
+pub fn with_furthest_failure<T>(tokens: &Tokens, parse: impl FnOnce() -> T) -> (T, Option<&Token>) {
+  FEWEST_LEFT.with(|left| left.set(usize::MAX));
+  let result = parse();
+  let left = FEWEST_LEFT.with(Cell::get);
+  (result, tokens.tokens.len().checked_sub(left).and_then(|i| tokens.tokens.get(i)))
+}
+
+// Where a comment starting at `i` ends, if one does and it is dropped. A "///" that begins its line
+// is a doc comment and is kept, as is a "/*" that is never closed.
+fn comment_end(list: &[u8], i: usize, after_token: bool) -> Option<usize> {
+  if list[i..].starts_with(b"/*") {
+    list[i + 2..].windows(2).position(|pair| pair == b"*/").map(|end| i + 2 + end + 2)
+  } else if list[i..].starts_with(b"//") && (after_token || !list[i..].starts_with(b"///")) {
+    Some(list[i..].iter().position(|c| *c == b'\n' || *c == b'\r').map_or(list.len(), |end| i + end))
+  } else {
+    None
+  }
+}
+
+pub fn lex(input: &str) -> Tokens {
+  let mut tokens = Tokens::new();
+  let list = input.as_bytes();
+  let mut i = 0;
+  let mut line = 1;
+  let mut col = 1;
+  let mut diff = 0;
+  // the kind of quote (Quote or TripleQuote) of the string literal being lexed, if any
+  let mut open_quote: Option<TokenKind> = None;
+  // the line of a doc comment, which is kept as tokens
+  let mut comment_line = None;
+  while i < list.len() {
+    let start = i;
+    let c = list[i];
+    //a /* block comment */ or a // line comment is dropped like whitespace, wherever it is. A block
+    //comment that is never closed is lexed as tokens, for validate to report
+    if open_quote.is_none() && comment_line != Some(line) {
+      if let Some(end) = comment_end(list, i, tokens.tokens.last().is_some_and(|token| token.end_line == line)) {
+        while i < end {
+          if list[i] == 10 || (list[i] == 13 && list.get(i + 1) != Some(&10)) {
+            line += 1;
+            col = 1;
+          } else {
+            col += 1;
+          }
+          i += 1;
+        }
+        continue;
+      }
+    }
+    let mut kind = match c {
+        48..=57 => TokenKind::Digit,
+        65..=90 | 97..=122 => TokenKind::Alpha,
+        32 | 10 | 13 | 9 => TokenKind::WhiteSpace,
+        61 => TokenKind::Equal,
+        59 => TokenKind::Semicolon,
+        123 => TokenKind::LeftCurly,
+        125 => TokenKind::RightCurly,
+        40 => TokenKind::LeftParen,
+        41 => TokenKind::RightParen,
+        43 => TokenKind::Plus,
+        45 => TokenKind::Dash,
+        44 => TokenKind::Comma,
+        34 => TokenKind::Quote,
+        47 => TokenKind::Slash,
+        42 => TokenKind::Star,
+        58 => TokenKind::Colon,
+        91 => TokenKind::LeftBracket,
+        93 => TokenKind::RightBracket,
+        46 => TokenKind::Dot,
+        95 => TokenKind::Underscore,
+        60 => TokenKind::Less,
+        62 => TokenKind::Greater,
+        38 => TokenKind::Ampersand,
+        124 => TokenKind::Pipe,
+        94 => TokenKind::Caret,
+        _x => TokenKind::Other,
+    };
+    //check if fn, true, false, let or return, only as whole words so identifiers like `trueness` still lex as letters
+    if is_word_at(list, i, b"fn") {
+      kind = TokenKind::Fn;
+      i += 1;
+      diff = 1;
+    }
+    if is_word_at(list, i, b"true") {
+      kind = TokenKind::True;
+      i += 3;
+      diff = 3;
+    }
+    if is_word_at(list, i, b"false") {
+      kind = TokenKind::False;
+      i += 4;
+      diff = 4;
+    }
+    if is_word_at(list, i, b"let") {
+      kind = TokenKind::Let;
+      i += 2;
+      diff = 2;
+    }
+    if is_word_at(list, i, b"return") {
+      kind = TokenKind::Return;
+      i += 5;
+      diff = 5;
+    }
+    //check if if, else, while, match, rec, const, loop or break, only as whole words so identifiers like `iffy` still lex as letters.
+    //rec is only a keyword between let and the name it binds, anywhere else it is an ordinary name
+    if is_word_at(list, i, b"if") {
+      kind = TokenKind::If;
+      i += 1;
+      diff = 1;
+    }
+    if is_word_at(list, i, b"else") {
+      kind = TokenKind::Else;
+      i += 3;
+      diff = 3;
+    }
+    if is_word_at(list, i, b"while") {
+      kind = TokenKind::While;
+      i += 4;
+      diff = 4;
+    }
+    if is_word_at(list, i, b"match") {
+      kind = TokenKind::Match;
+      i += 4;
+      diff = 4;
+    }
+    if is_word_at(list, i, b"rec") && tokens.tokens.last().is_some_and(|token| token.kind == TokenKind::Let)
+      && list[i + 3..].iter().find(|c| !c.is_ascii_whitespace()).is_some_and(|c| c.is_ascii_alphabetic() || *c == b'_') {
+      kind = TokenKind::Rec;
+      i += 2;
+      diff = 2;
+    }
+    if is_word_at(list, i, b"const") {
+      kind = TokenKind::Const;
+      i += 4;
+      diff = 4;
+    }
+    if is_word_at(list, i, b"loop") {
+      kind = TokenKind::Loop;
+      i += 3;
+      diff = 3;
+    }
+    if is_word_at(list, i, b"break") {
+      kind = TokenKind::Break;
+      i += 4;
+      diff = 4;
+    }
+    //check if r#name, a raw identifier that may be spelled like a keyword. Its lexeme is the name without r#
+    let mut lexeme_start = start;
+    if c == b'r' && open_quote.is_none() && list.get(i + 1) == Some(&b'#')
+      && list.get(i + 2).is_some_and(|c| c.is_ascii_alphabetic() || *c == b'_') {
+      let length = list[i + 2..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == b'_').count();
+      kind = TokenKind::RawIdentifier;
+      lexeme_start = i + 2;
+      i += 1 + length;
+      diff = 1 + length as u32;
+    }
+    //check if r", the start of a raw string
+    if c == b'r' && open_quote.is_none() && list.get(i + 1) == Some(&b'"') && is_word_at(list, i, b"r") {
+      kind = TokenKind::RawQuote;
+      i += 1;
+      diff = 1;
+    }
+    //check for two character operators
+    let operator = match list.get(i..i + 2) {
+      Some(b"==") => Some(TokenKind::EqualEqual),
+      Some(b"!=") => Some(TokenKind::NotEqual),
+      Some(b"<=") => Some(TokenKind::LessEqual),
+      Some(b">=") => Some(TokenKind::GreaterEqual),
+      Some(b"&&") => Some(TokenKind::AndAnd),
+      Some(b"||") => Some(TokenKind::OrOr),
+      Some(b"<<") => Some(TokenKind::ShiftLeft),
+      Some(b">>") => Some(TokenKind::ShiftRight),
+      Some(b"=>") => Some(TokenKind::FatArrow),
+      Some(b"|>") => Some(TokenKind::PipeForward),
+      Some(b"+=") => Some(TokenKind::PlusEqual),
+      _ => None,
+    };
+    if let Some(operator) = operator {
+      kind = operator;
+      i += 1;
+      diff = 1;
+    }
+    //check if """ (a plain string can't contain it, its second quote ends the string)
+    if c == b'"' && open_quote != Some(TokenKind::Quote) && list.get(i + 1) == Some(&b'"') && list.get(i + 2) == Some(&b'"') {
+      kind = TokenKind::TripleQuote;
+      i += 2;
+      diff = 2;
+    }
+    //track whether we are inside a string literal, a quote only closes a string it opened. A quote
+    //in a line comment opens nothing
+    let in_string = open_quote.is_some();
+    match open_quote {
+      _ if comment_line == Some(line) => {},
+      None if kind == TokenKind::Quote || kind == TokenKind::TripleQuote => open_quote = Some(kind),
+      None if kind == TokenKind::RawQuote => open_quote = Some(TokenKind::Quote),
+      Some(open) if open == kind => open_quote = None,
+      _ => {},
+    }
+
+    //track doc comments outside of string literals
+    if !in_string && comment_line != Some(line) && kind == TokenKind::Slash && list.get(i + 1) == Some(&b'/') {
+      comment_line = Some(line);
+    }
+
+    //create token struct
+    let token = Token {
+        kind,
+        lexeme: list[lexeme_start..=i].to_vec(),
+        start_col: col,
+        end_col: col + diff,
+        start_line: line,
+        end_line: line,
+        start_offset: start,
+        end_offset: i + 1,
+    };
+    i +=1;
+    // whitespace only matters inside string literals
+    if kind != TokenKind::WhiteSpace || in_string {
+      tokens.push(token);
+    }
+    col += 1 + diff;
+    diff = 0;
+    // a line ends at "\n", or at a "\r" not followed by "\n" ("\r\n" ends on its "\n")
+    if c == 10 || (c == 13 && list.get(i) != Some(&10)) {
+      line +=1;
+      col = 1;
+    }
+  }
+
+  let token = Token {
+    kind: TokenKind::EOF,
+    lexeme: vec![],
+    start_col: col,
+    end_col: col ,
+    start_line: line,
+    end_line: line,
+    start_offset: list.len(),
+    end_offset: list.len(),
+    };
+  tokens.push(token);
+  tokens
+}
+
+// The text of the source between two byte offsets, such as a token's offsets or a parser Span,
+// for showing the code an error is about. The range is cut short at the end of the source, and
+// an offset inside a multi-byte character moves to the start of that character.
+pub fn snippet(source: &str, span: (usize, usize)) -> &str {
+  let boundary = |mut offset: usize| {
+    offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+      offset -= 1;
+    }
+    offset
+  };
+  let (start, end) = (boundary(span.0), boundary(span.1));
+  &source[start..end.max(start)]
+}
+
+// The number of tokens a parser left unconsumed, not counting the end of input marker. It is 0
+// when everything was parsed.
+pub fn remaining_tokens(tokens: &Tokens) -> usize {
+  tokens.tokens.iter().filter(|token| token.kind != TokenKind::EOF).count()
+}
+
+// The kind of the first token a parser left unconsumed, the one it could not make sense of. None
+// when everything was parsed.
+pub fn first_remaining_kind(tokens: &Tokens) -> Option<TokenKind> {
+  tokens.tokens.first().map(|token| token.kind).filter(|kind| *kind != TokenKind::EOF)
+}
+
+// Whether `word` starts at `i` and is not part of a longer identifier.
+fn is_word_at(list: &[u8], i: usize, word: &[u8]) -> bool {
+  let is_ident = |c: &u8| c.is_ascii_alphanumeric() || *c == b'_';
+  list[i..].starts_with(word)
+    && (i == 0 || !is_ident(&list[i - 1]))
+    && !list.get(i + word.len()).is_some_and(is_ident)
+}
+
+// Checks lexed tokens for mistakes that would otherwise surface as an unhelpful parse failure:
+// a string literal or block comment that is never closed, or a bracket, brace or paren without
+// its partner.
+// Strings and comments are skipped, brackets inside them don't count.
+pub fn validate(tokens: &Tokens) -> Result<(), AsaErrorKind> {
+  let mut open_quote: Option<&Token> = None;
+  let mut open_brackets: Vec<&Token> = vec![];
+  let mut comment_line = None;
+  let position = |token: &Token| format!("line {}, column {}", token.start_line, token.start_col);
+  for (i, token) in tokens.tokens.iter().enumerate() {
+    if comment_line == Some(token.start_line) {
+      continue;
+    }
+    comment_line = None;
+    match open_quote {
+      None if token.kind == TokenKind::Quote || token.kind == TokenKind::TripleQuote
+        || token.kind == TokenKind::RawQuote => open_quote = Some(token),
+      Some(open) if open.kind == token.kind || (open.kind == TokenKind::RawQuote && token.kind == TokenKind::Quote) => open_quote = None,
+      Some(_) => {},
+      None => match token.kind {
+        TokenKind::Slash if tokens.tokens.get(i + 1).is_some_and(|next| next.kind == TokenKind::Slash) => {
+          comment_line = Some(token.start_line);
+        },
+        // the lexer drops every block comment that is closed
+        TokenKind::Slash if tokens.tokens.get(i + 1).is_some_and(|next| next.kind == TokenKind::Star && next.start_offset == token.end_offset) => {
+          return Err(AsaErrorKind::ParseError(format!("Unterminated block comment starting at {}", position(token))));
+        },
+        TokenKind::LeftParen | TokenKind::LeftCurly | TokenKind::LeftBracket => open_brackets.push(token),
+        TokenKind::RightParen | TokenKind::RightCurly | TokenKind::RightBracket => {
+          let partner = match token.kind {
+            TokenKind::RightParen => TokenKind::LeftParen,
+            TokenKind::RightCurly => TokenKind::LeftCurly,
+            _ => TokenKind::LeftBracket,
+          };
+          match open_brackets.pop() {
+            Some(open) if open.kind == partner => {},
+            // the innermost open bracket is the one missing its partner
+            Some(open) => return Err(AsaErrorKind::ParseError(format!(
+              "Unclosed {} at {}", String::from_utf8_lossy(&open.lexeme), position(open)
+            ))),
+            None => return Err(AsaErrorKind::ParseError(format!(
+              "Unmatched {} at {}", String::from_utf8_lossy(&token.lexeme), position(token)
+            ))),
+          }
+        },
+        _ => {},
+      },
+    }
+  }
+  if let Some(open) = open_quote {
+    return Err(AsaErrorKind::ParseError(format!("Unterminated string starting at {}", position(open))));
+  }
+  match open_brackets.pop() {
+    Some(open) => Err(AsaErrorKind::ParseError(format!(
+      "Unclosed {} at {}", String::from_utf8_lossy(&open.lexeme), position(open)
+    ))),
+    None => Ok(()),
+  }
+}
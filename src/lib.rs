@@ -1,11 +1,15 @@
-extern crate nom;
-
-pub mod interpreter;
-pub mod parser;
-pub mod error;
-pub mod lexer;
-
-pub use self::parser::*;
-pub use self::interpreter::*;
-pub use self::lexer::*;
-pub use self::error::*;
\ No newline at end of file
+extern crate nom;
+
+pub mod interpreter;
+pub mod parser;
+pub mod error;
+pub mod lexer;
+pub mod builtins;
+pub mod visitor;
+mod checker;
+
+pub use self::parser::*;
+pub use self::interpreter::*;
+pub use self::lexer::*;
+pub use self::error::*;
+pub use self::visitor::*;
\ No newline at end of file
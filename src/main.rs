@@ -3,20 +3,29 @@ extern crate asalang;
 
 use asalang::*;
 
-fn main() -> Result<(), AsaErrorKind> {
-  
-  let tokens = lex("123");
-  match program(tokens) {
-    Ok((tokens, tree)) => {
-      println!("{:?}", tokens);
-      println!("Tree: {:#?}", tree);
-      let mut interpreter = Interpreter::new();
-      let result = interpreter.exec(&tree);
-      println!("Interpreter Result: {:?}", result);
-    },
-    Err(e) => println!("Error: {:?}", e),
+// Runs the Asa program in the file given as the only argument and prints its result.
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+  let path = match args.as_slice() {
+    [_, path] => path,
+    _ => {
+      eprintln!("Usage: {} <file.asa>", args.first().map_or("asalang", |name| name.as_str()));
+      std::process::exit(2);
+    }
+  };
+  let source = match std::fs::read_to_string(path) {
+    Ok(source) => source,
+    Err(e) => {
+      eprintln!("Error: cannot read {}: {}", path, e);
+      std::process::exit(1);
+    }
+  };
+  let mut interpreter = Interpreter::new();
+  match interpreter.run_source(&source) {
+    Ok(value) => println!("{}", value),
+    Err(e) => {
+      eprintln!("Error: {}", e);
+      std::process::exit(1);
+    }
   }
-
-    
-  Ok(())
 }
@@ -1,365 +1,1300 @@
-// Here is where the various combinators are imported. You can find all the combinators here:
-// https://docs.rs/nom/7.1.3/nom/
-
-//use nom::*;
-use crate::lexer::*;
-// use nom::sequence::tuple;
-// use nom::combinator::map;
-
-
- use nom::{
-  IResult,
-  branch::alt,
-  // combinator::opt,
-  multi::{many1, many0},
-  // bytes::complete::{tag},
-  // character::complete::{alphanumeric1, digit1},
-};
- 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Node {
-  Program { children: Vec<Node> },
-  Statement { children: Vec<Node> },
-  FunctionDefine {name: Vec<u8>, children: Vec<Node> },
-  FunctionArguments { children: Vec<Node> },
-  FunctionStatements { children: Vec<Node> },
-  Expression { children: Vec<Node> },
-  MathExpression {name: Vec<u8>, children: Vec<Node> },
-  FunctionCall { name: Vec<u8>, children: Vec<Node> },
-  VariableDefine { children: Vec<Node> },
-  FunctionReturn { children: Vec<Node> },
-  Number { value: i32 },
-  Bool { value: bool },
-  Identifier { value: Vec<u8> },
-  String { value: String },
-  Comment { value: Vec<u8> },
-  Null,
-}
-
-// Some helper functions to use Tokens instead of a &str with Nom. 
-// You'll probably have to create more of these as needed.
-
-pub fn t_alpha(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Alpha => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_digit(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Digit => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_true(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::True => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_false(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::False => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_alpha1(input: Tokens) -> IResult<Tokens, Vec<Token>> {
-  many1(t_alpha)(input)
-}
-
-pub fn t_alpha0(input: Tokens) -> IResult<Tokens, Vec<Token>> {
-  many0(t_alpha)(input)
-}
-
-pub fn t_alphanumeric1(input: Tokens) -> IResult<Tokens, Vec<Token>> {
-  many1(alt((t_alpha,t_digit)))(input)
-}
-
-pub fn t_alphanumeric0(input: Tokens) -> IResult<Tokens, Vec<Token>> {
-  many0(alt((t_alpha,t_digit,)))(input)
-
-}
-
-// keywords 
-
-pub fn t_left_paren(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::LeftParen => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
-}
-
-pub fn t_right_paren(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::RightParen => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
-}
-
-// Helper function to parse the curly brackets
-pub fn t_left_curly(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::LeftCurly => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
-}
-
-pub fn t_right_curly(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::RightCurly => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
-}
-
-pub fn t_quote(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Quote => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_slash(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Slash => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_comma(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::Comma => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
-}
-
-pub fn t_semicolon(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::Semicolon => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
-}
-
-
-pub fn t_let(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Let => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_fn(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Fn => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_return(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Return => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_whitespace(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::WhiteSpace => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_plus(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Plus => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_dash(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Dash => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn t_equal(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Equal => true,
-    _ => false,
-  });
-  fxn(input.clone())
-}
-
-pub fn identifier(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, first) = t_alpha(input)?;
-  let (input, rest) = t_alphanumeric0(input)?;
-  let mut identifier = first.lexeme;
-  for mut tk in rest {
-    identifier.append(&mut tk.lexeme);
-  }
-  Ok((input,Node::Identifier{value: identifier}))
-}
-
-pub fn number(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, digits) = many1(t_digit)(input)?;
-  let value: Vec<u8> = digits.iter()
-                             .flat_map(|token| token.lexeme.iter())
-                             .cloned()
-                             .collect();
-  let parsed_value: i32 = std::str::from_utf8(&value).unwrap().parse::<i32>().unwrap();
-  Ok((input, Node::Number { value: parsed_value }))
-}
-
-pub fn boolean(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, token) = alt((t_true, t_false))(input)?;
-   let value = match token.kind {
-      TokenKind::True => true,
-      TokenKind::False => false,
-      _ => unreachable!(),
-  };
-  Ok((input, Node::Bool { value }))
-}
-
-pub fn string(input: Tokens) -> IResult<Tokens, Node> {
- let (input, _) = t_quote(input)?;
-  let (input, string) = t_alphanumeric0(input)?;
- let (input, _) = t_quote(input)?;
- let value: Vec<u8> = string.into_iter()
-                               .map(|token| token.lexeme)
-                               .flatten()
-                               .collect();
- Ok((input, Node::String{ value: String::from_utf8(value).unwrap() }))
-}
-
-pub fn function_call(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, fxn_name) = identifier(input)?;
-  let (input, _) = (t_left_paren)(input)?;
-  let (input, args) = many0(arguments)(input)?;
-  let (input, _) = (t_right_paren)(input)?;
-  let args = if args.is_empty() {
-    vec![Node::FunctionArguments{ children: vec![]}]
-  } else {
-    args
-  };
-  let name: Vec<u8> = match fxn_name {
-    Node::Identifier{value} => value,
-    _ => unreachable!(),
-  }; 
-  Ok((input, Node::FunctionCall{name, children: args}))
-}
-
-pub fn value(input: Tokens) -> IResult<Tokens, Node> {
-  alt((number, identifier, boolean))(input)
-}
-
-pub fn math_expression(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, leftside) = value(input)?;
-  let (input, operator) = alt((t_plus, t_dash))(input)?;
-  let (input, rightside) = value(input)?;
-  let name = match operator.kind {
-    TokenKind::Plus => b"add",
-    TokenKind::Dash => b"sub",
-    _ => unreachable!(),
-  };
-  Ok((input, Node::MathExpression{name: name.to_vec(), children: vec![leftside, rightside] }))
-}
-
-
-pub fn expression(input: Tokens) -> IResult<Tokens, Node> {
-   let (input, result) =  alt((boolean, math_expression, function_call, number, string,identifier))(input)?;
-   Ok((input, Node::Expression{children: vec! [result]}))
-}
-
-pub fn statement(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, result) = alt((variable_define, expression, function_return))(input)?;
-  let (input, _) = (t_semicolon)(input)?;
-  Ok((input, result))
-}
-
-pub fn function_return(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, _) = t_return(input)?;
-  let (input, result) = alt((function_call,expression, identifier))(input)?;
-  Ok((input, Node::FunctionReturn{children: vec! [result]}))
-}
-
-pub fn variable_define(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, _) = t_let(input)?;
-  let (input, variable) = identifier(input)?;
-  let (input, _) = (t_equal)(input)?;
-  let (input, expression) = expression(input)?;
-  Ok((input, Node::VariableDefine{children: vec![variable,expression]}))
-}
-
-pub fn arguments(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, arg) = expression(input)?;
-  let (input, mut others) = many0(other_arg) (input)?;
-  let mut args = vec! [arg];
-  args.append (&mut others) ;
-  Ok((input, Node::FunctionArguments{children: args}))
-}
-
-
-pub fn other_arg(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, _) = t_comma(input)?;
-  expression(input)
-}
-
-pub fn function_define(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, _) = t_fn(input)?;
-  let (input, fxn_name) = identifier(input)?;
-  let name = match fxn_name {
-    Node::Identifier{value} => value,
-    _ => unreachable!(),
-  };
-  let (input, _) = t_left_paren(input)?;
-  let (input, args) = many0(arguments)(input)?;
-  let (input, _) = t_right_paren(input)?;
-  let (input, _) = t_left_curly(input)?;
-  let (input, statements) = many1(statement)(input)?;
-  let (input, _) = t_right_curly(input)?;
-  let fxn_statements = Node::FunctionStatements{children: statements};
-  let fxn_arguments = if args.is_empty() {
-    Node::FunctionArguments{children: vec![]}
-  } else {
-    args[0].clone()
-  };
-  Ok((input, Node::FunctionDefine{name, children: vec![fxn_arguments,fxn_statements] }))
-}
-
-pub fn comment(input: Tokens) -> IResult<Tokens, Node> {
-  let mut comment_text = Vec::new();
-  let (input, _) = t_slash(input)?;
-  let (input, _) = t_slash(input)?;
-  let (input, alpha_tokens) = many0(t_alpha)(input)?;
-  for token in alpha_tokens {
-    comment_text.extend_from_slice(&token.lexeme);
-  }
-  Ok((input, Node::Comment{ value: comment_text }))
-}
-
-pub fn program(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, result) = many1(alt((function_define,expression,statement,string,boolean,number)))(input)?;
-  Ok((input, Node::Program{ children: result }))
-}
\ No newline at end of file
+// Here is where the various combinators are imported. You can find all the combinators here:
+// https://docs.rs/nom/7.1.3/nom/
+
+//use nom::*;
+use crate::lexer::*;
+// use nom::sequence::tuple;
+// use nom::combinator::map;
+
+
+ use nom::{
+  IResult,
+  branch::alt,
+  combinator::{opt, fail},
+  sequence::{pair, tuple},
+  multi::{many1, many0},
+  // bytes::complete::{tag},
+  // character::complete::{alphanumeric1, digit1},
+};
+ 
+// A byte range of the source, the end is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+  Program { children: Vec<Node> },
+  Statement { children: Vec<Node> },
+  FunctionDefine {name: Vec<u8>, children: Vec<Node> },
+  FunctionArguments { children: Vec<Node> },
+  FunctionStatements { children: Vec<Node> },
+  Expression { children: Vec<Node> },
+  MathExpression {name: Vec<u8>, children: Vec<Node> },
+  UnaryExpression {name: Vec<u8>, children: Vec<Node> },
+  FunctionCall { name: Vec<u8>, children: Vec<Node> },
+  NamedArgument { children: Vec<Node> },
+  // The name and value, followed by a TypeAnnotation when the variable has one.
+  VariableDefine { children: Vec<Node> },
+  // `const name = value`, with the same children as a VariableDefine. The variable cannot be changed afterwards.
+  ConstDefine { children: Vec<Node> },
+  // `let [a, b] = value`: the Identifiers to bind, followed by the value, which must be a list of as many elements.
+  Destructure { children: Vec<Node> },
+  // `let rec name = value`, binding a function value that can call itself by that name.
+  RecursiveDefine { children: Vec<Node> },
+  // An anonymous function: its FunctionArguments and FunctionStatements.
+  Lambda { children: Vec<Node> },
+  // A function parameter with a type annotation: its name and the TypeAnnotation.
+  Parameter { children: Vec<Node> },
+  // The type named after a ":" in `let x: number` or `fn f(a: number)`.
+  TypeAnnotation { value: Vec<u8> },
+  Assignment { children: Vec<Node> },
+  FunctionReturn { children: Vec<Node> },
+  Number { value: i32 },
+  Float { value: f64 },
+  Bool { value: bool },
+  Identifier { value: Vec<u8> },
+  String { value: String },
+  Comment { value: Vec<u8> },
+  // The text of the `///` lines documenting a function, the last child of its FunctionDefine.
+  DocComment { value: String },
+  Map { children: Vec<Node> },
+  MapEntry { children: Vec<Node> },
+  Index { children: Vec<Node> },
+  List { children: Vec<Node> },
+  If { children: Vec<Node> },
+  While { children: Vec<Node> },
+  // `while let`: the variable, the expression bound to it before each run and the body.
+  WhileLet { children: Vec<Node> },
+  // `loop`: the body, run until a break leaves it.
+  Loop { children: Vec<Node> },
+  // `break`: the loop's value, if there is one.
+  Break { children: Vec<Node> },
+  // The value matched on, followed by MatchArm nodes.
+  Match { children: Vec<Node> },
+  // A pattern and the expression evaluated when it matches.
+  MatchArm { children: Vec<Node> },
+  // The `_` pattern, which matches any value.
+  Wildcard,
+  Block { children: Vec<Node> },
+  // The source range its single child was parsed from, so runtime errors can point at it.
+  Spanned { span: Span, children: Vec<Node> },
+  Null,
+}
+
+// Equality is structural and ignores Spanned wrappers, so a parsed tree equals the same tree
+// written out by hand without spans.
+impl PartialEq for Node {
+  fn eq(&self, other: &Node) -> bool {
+    match (self.unspanned(), other.unspanned()) {
+      (Node::Number{value: a}, Node::Number{value: b}) => a == b,
+      (Node::Float{value: a}, Node::Float{value: b}) => a == b,
+      (Node::Bool{value: a}, Node::Bool{value: b}) => a == b,
+      (Node::Identifier{value: a}, Node::Identifier{value: b}) => a == b,
+      (Node::String{value: a}, Node::String{value: b}) => a == b,
+      (Node::Comment{value: a}, Node::Comment{value: b}) => a == b,
+      (Node::DocComment{value: a}, Node::DocComment{value: b}) => a == b,
+      (Node::TypeAnnotation{value: a}, Node::TypeAnnotation{value: b}) => a == b,
+      (Node::FunctionDefine{name: a, children: ac}, Node::FunctionDefine{name: b, children: bc})
+      | (Node::MathExpression{name: a, children: ac}, Node::MathExpression{name: b, children: bc})
+      | (Node::UnaryExpression{name: a, children: ac}, Node::UnaryExpression{name: b, children: bc})
+      | (Node::FunctionCall{name: a, children: ac}, Node::FunctionCall{name: b, children: bc}) => a == b && ac == bc,
+      (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b) && a.children() == b.children(),
+    }
+  }
+}
+
+impl Node {
+  // The child nodes of this node, empty for leaves.
+  pub fn children(&self) -> &[Node] {
+    match self {
+      Node::Program{children}
+      | Node::Statement{children}
+      | Node::FunctionDefine{children, ..}
+      | Node::FunctionArguments{children}
+      | Node::FunctionStatements{children}
+      | Node::Expression{children}
+      | Node::MathExpression{children, ..}
+      | Node::UnaryExpression{children, ..}
+      | Node::FunctionCall{children, ..}
+      | Node::NamedArgument{children}
+      | Node::VariableDefine{children}
+      | Node::ConstDefine{children}
+      | Node::Destructure{children}
+      | Node::Parameter{children}
+      | Node::RecursiveDefine{children}
+      | Node::Lambda{children}
+      | Node::Assignment{children}
+      | Node::FunctionReturn{children}
+      | Node::Map{children}
+      | Node::MapEntry{children}
+      | Node::Index{children}
+      | Node::List{children}
+      | Node::If{children}
+      | Node::While{children}
+      | Node::WhileLet{children}
+      | Node::Loop{children}
+      | Node::Break{children}
+      | Node::Match{children}
+      | Node::MatchArm{children}
+      | Node::Block{children}
+      | Node::Spanned{children, ..} => children,
+      Node::Number{..}
+      | Node::Float{..}
+      | Node::Bool{..}
+      | Node::Identifier{..}
+      | Node::String{..}
+      | Node::Comment{..}
+      | Node::DocComment{..}
+      | Node::TypeAnnotation{..}
+      | Node::Wildcard
+      | Node::Null => &[],
+    }
+  }
+
+  // The child nodes of this node, for rewriting them in place.
+  fn children_mut(&mut self) -> &mut [Node] {
+    match self {
+      Node::Program{children}
+      | Node::Statement{children}
+      | Node::FunctionDefine{children, ..}
+      | Node::FunctionArguments{children}
+      | Node::FunctionStatements{children}
+      | Node::Expression{children}
+      | Node::MathExpression{children, ..}
+      | Node::UnaryExpression{children, ..}
+      | Node::FunctionCall{children, ..}
+      | Node::NamedArgument{children}
+      | Node::VariableDefine{children}
+      | Node::ConstDefine{children}
+      | Node::Destructure{children}
+      | Node::Parameter{children}
+      | Node::RecursiveDefine{children}
+      | Node::Lambda{children}
+      | Node::Assignment{children}
+      | Node::FunctionReturn{children}
+      | Node::Map{children}
+      | Node::MapEntry{children}
+      | Node::Index{children}
+      | Node::List{children}
+      | Node::If{children}
+      | Node::While{children}
+      | Node::WhileLet{children}
+      | Node::Loop{children}
+      | Node::Break{children}
+      | Node::Match{children}
+      | Node::MatchArm{children}
+      | Node::Block{children}
+      | Node::Spanned{children, ..} => children,
+      Node::Number{..}
+      | Node::Float{..}
+      | Node::Bool{..}
+      | Node::Identifier{..}
+      | Node::String{..}
+      | Node::Comment{..}
+      | Node::DocComment{..}
+      | Node::TypeAnnotation{..}
+      | Node::Wildcard
+      | Node::Null => &mut [],
+    }
+  }
+
+  // This tree without the Expression wrappers that hold a single node, which only add depth.
+  // Every such wrapper is replaced by the node it holds, the tree evaluates the same. The wrapper
+  // of an expression statement in a block stays, it is what makes it a statement.
+  pub fn flatten(self) -> Node {
+    let mut node = match self {
+      Node::Expression{mut children} if children.len() == 1 => return children.remove(0).flatten(),
+      Node::Statement{mut children} if matches!(children.as_slice(), [Node::Expression{..}]) => {
+        for child in children[0].children_mut() {
+          *child = std::mem::replace(child, Node::Null).flatten();
+        }
+        return Node::Statement{children};
+      }
+      node => node,
+    };
+    for child in node.children_mut() {
+      *child = std::mem::replace(child, Node::Null).flatten();
+    }
+    node
+  }
+
+  // The node inside any Spanned wrappers.
+  pub fn unspanned(&self) -> &Node {
+    match self {
+      Node::Spanned{children, ..} if children.len() == 1 => children[0].unspanned(),
+      _ => self,
+    }
+  }
+
+  // The name of the node's variant, for use in error messages.
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Node::Program{..} => "Program",
+      Node::Statement{..} => "Statement",
+      Node::FunctionDefine{..} => "FunctionDefine",
+      Node::FunctionArguments{..} => "FunctionArguments",
+      Node::FunctionStatements{..} => "FunctionStatements",
+      Node::Expression{..} => "Expression",
+      Node::MathExpression{..} => "MathExpression",
+      Node::UnaryExpression{..} => "UnaryExpression",
+      Node::FunctionCall{..} => "FunctionCall",
+      Node::NamedArgument{..} => "NamedArgument",
+      Node::VariableDefine{..} => "VariableDefine",
+      Node::ConstDefine{..} => "ConstDefine",
+      Node::Destructure{..} => "Destructure",
+      Node::Parameter{..} => "Parameter",
+      Node::RecursiveDefine{..} => "RecursiveDefine",
+      Node::Lambda{..} => "Lambda",
+      Node::TypeAnnotation{..} => "TypeAnnotation",
+      Node::Assignment{..} => "Assignment",
+      Node::FunctionReturn{..} => "FunctionReturn",
+      Node::Number{..} => "Number",
+      Node::Float{..} => "Float",
+      Node::Bool{..} => "Bool",
+      Node::Identifier{..} => "Identifier",
+      Node::String{..} => "String",
+      Node::Comment{..} => "Comment",
+      Node::DocComment{..} => "DocComment",
+      Node::Map{..} => "Map",
+      Node::MapEntry{..} => "MapEntry",
+      Node::Index{..} => "Index",
+      Node::List{..} => "List",
+      Node::If{..} => "If",
+      Node::While{..} => "While",
+      Node::WhileLet{..} => "WhileLet",
+      Node::Loop{..} => "Loop",
+      Node::Break{..} => "Break",
+      Node::Match{..} => "Match",
+      Node::MatchArm{..} => "MatchArm",
+      Node::Wildcard => "Wildcard",
+      Node::Block{..} => "Block",
+      Node::Spanned{..} => "Spanned",
+      Node::Null => "Null",
+    }
+  }
+}
+
+// Some helper functions to use Tokens instead of a &str with Nom. 
+// You'll probably have to create more of these as needed.
+
+pub fn t_alpha(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Alpha));
+  fxn(input.clone())
+}
+
+pub fn t_digit(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Digit));
+  fxn(input.clone())
+}
+
+pub fn t_true(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::True));
+  fxn(input.clone())
+}
+
+pub fn t_false(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::False));
+  fxn(input.clone())
+}
+
+pub fn t_alpha1(input: Tokens) -> IResult<Tokens, Vec<Token>> {
+  many1(t_alpha)(input)
+}
+
+pub fn t_alpha0(input: Tokens) -> IResult<Tokens, Vec<Token>> {
+  many0(t_alpha)(input)
+}
+
+pub fn t_alphanumeric1(input: Tokens) -> IResult<Tokens, Vec<Token>> {
+  many1(alt((t_alpha,t_digit)))(input)
+}
+
+pub fn t_alphanumeric0(input: Tokens) -> IResult<Tokens, Vec<Token>> {
+  many0(alt((t_alpha,t_digit,)))(input)
+
+}
+
+// keywords 
+
+pub fn t_left_paren(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::LeftParen));
+  fxn(input.clone())
+}
+
+pub fn t_right_paren(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::RightParen));
+  fxn(input.clone())
+}
+
+// Helper function to parse the curly brackets
+pub fn t_left_curly(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::LeftCurly));
+  fxn(input.clone())
+}
+
+pub fn t_right_curly(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::RightCurly));
+  fxn(input.clone())
+}
+
+pub fn t_quote(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Quote));
+  fxn(input.clone())
+}
+
+pub fn t_slash(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Slash));
+  fxn(input.clone())
+}
+
+pub fn t_star(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Star));
+  fxn(input.clone())
+}
+
+pub fn t_comma(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::Comma));
+  fxn(input.clone())
+}
+
+pub fn t_colon(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::Colon));
+  fxn(input.clone())
+}
+
+pub fn t_left_bracket(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::LeftBracket));
+  fxn(input.clone())
+}
+
+pub fn t_right_bracket(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::RightBracket));
+  fxn(input.clone())
+}
+
+pub fn t_semicolon(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(&|tk| matches!(tk.kind, TokenKind::Semicolon));
+  fxn(input.clone())
+}
+
+
+pub fn t_let(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Let));
+  fxn(input.clone())
+}
+
+pub fn t_fn(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Fn));
+  fxn(input.clone())
+}
+
+pub fn t_return(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Return));
+  fxn(input.clone())
+}
+
+pub fn t_whitespace(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::WhiteSpace));
+  fxn(input.clone())
+}
+
+pub fn t_plus(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Plus));
+  fxn(input.clone())
+}
+
+pub fn t_dash(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Dash));
+  fxn(input.clone())
+}
+
+pub fn t_equal(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Equal));
+  fxn(input.clone())
+}
+
+pub fn t_dot(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Dot));
+  fxn(input.clone())
+}
+
+pub fn t_equal_equal(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::EqualEqual));
+  fxn(input.clone())
+}
+
+pub fn t_not_equal(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::NotEqual));
+  fxn(input.clone())
+}
+
+pub fn t_less(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Less));
+  fxn(input.clone())
+}
+
+pub fn t_greater(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Greater));
+  fxn(input.clone())
+}
+
+pub fn t_less_equal(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::LessEqual));
+  fxn(input.clone())
+}
+
+pub fn t_greater_equal(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::GreaterEqual));
+  fxn(input.clone())
+}
+
+pub fn t_and_and(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::AndAnd));
+  fxn(input.clone())
+}
+
+pub fn t_or_or(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::OrOr));
+  fxn(input.clone())
+}
+
+pub fn t_ampersand(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Ampersand));
+  fxn(input.clone())
+}
+
+pub fn t_pipe(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Pipe));
+  fxn(input.clone())
+}
+
+pub fn t_pipe_forward(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::PipeForward));
+  fxn(input.clone())
+}
+
+pub fn t_plus_equal(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::PlusEqual));
+  fxn(input.clone())
+}
+
+pub fn t_caret(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Caret));
+  fxn(input.clone())
+}
+
+pub fn t_shift_left(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::ShiftLeft));
+  fxn(input.clone())
+}
+
+pub fn t_shift_right(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::ShiftRight));
+  fxn(input.clone())
+}
+
+pub fn t_if(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::If));
+  fxn(input.clone())
+}
+
+pub fn t_else(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Else));
+  fxn(input.clone())
+}
+
+pub fn t_while(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::While));
+  fxn(input.clone())
+}
+
+pub fn t_match(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Match));
+  fxn(input.clone())
+}
+
+pub fn t_const(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Const));
+  fxn(input.clone())
+}
+
+pub fn t_loop(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Loop));
+  fxn(input.clone())
+}
+
+pub fn t_break(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Break));
+  fxn(input.clone())
+}
+
+pub fn t_rec(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Rec));
+  fxn(input.clone())
+}
+
+pub fn t_fat_arrow(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::FatArrow));
+  fxn(input.clone())
+}
+
+pub fn t_underscore(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Underscore));
+  fxn(input.clone())
+}
+
+pub fn t_raw_identifier(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::RawIdentifier));
+  fxn(input.clone())
+}
+
+pub fn identifier(input: Tokens) -> IResult<Tokens, Node> {
+  if let Ok((input, raw)) = t_raw_identifier(input.clone()) {
+    return Ok((input, Node::Identifier{value: raw.lexeme}));
+  }
+  let (input, first) = alt((t_alpha, t_underscore))(input)?;
+  let (input, rest) = many0(alt((t_alpha, t_digit, t_underscore)))(input)?;
+  let mut identifier = first.lexeme;
+  for mut tk in rest {
+    identifier.append(&mut tk.lexeme);
+  }
+  Ok((input,Node::Identifier{value: identifier}))
+}
+
+pub fn number(input: Tokens) -> IResult<Tokens, Node> {
+  signed_number(input, false)
+}
+
+// Parses a number literal, negated when `negative` is set. The sign is applied before the
+// literal is converted so -2147483648 (i32::MIN) is representable. A literal that does not fit
+// an i32 is not a number.
+fn signed_number(input: Tokens, negative: bool) -> IResult<Tokens, Node> {
+  let (input, digits) = many1(t_digit)(input)?;
+  let (input, fraction) = opt(pair(t_dot, many1(t_digit)))(input)?;
+  let mut value: Vec<u8> = if negative { vec![b'-'] } else { vec![] };
+  value.extend(digits.iter().flat_map(|token| token.lexeme.iter()));
+  if let Some((_, fraction)) = fraction {
+    value.push(b'.');
+    value.extend(fraction.iter().flat_map(|token| token.lexeme.iter()));
+    let parsed_value: f64 = std::str::from_utf8(&value).unwrap().parse::<f64>().unwrap();
+    return Ok((input, Node::Float { value: parsed_value }));
+  }
+  match std::str::from_utf8(&value).unwrap().parse::<i32>() {
+    Ok(parsed_value) => Ok((input, Node::Number { value: parsed_value })),
+    Err(_) => fail(input),
+  }
+}
+
+pub fn boolean(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, token) = alt((t_true, t_false))(input)?;
+   let value = match token.kind {
+      TokenKind::True => true,
+      TokenKind::False => false,
+      _ => unreachable!(),
+  };
+  Ok((input, Node::Bool { value }))
+}
+
+pub fn t_triple_quote(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::TripleQuote));
+  fxn(input.clone())
+}
+
+pub fn t_raw_quote(input: Tokens) -> IResult<Tokens, Token> {
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::RawQuote));
+  fxn(input.clone())
+}
+
+// Any token up to the end of a string literal closed by the given quote kind.
+fn string_contents(quote: TokenKind) -> impl Fn(Tokens) -> IResult<Tokens, Vec<Token>> {
+  move |input: Tokens| many0(check_token(&|tk| tk.kind != quote && tk.kind != TokenKind::EOF))(input)
+}
+
+pub fn string(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, (_, string, _)) = alt((
+    tuple((t_quote, string_contents(TokenKind::Quote), t_quote)),
+    tuple((t_triple_quote, string_contents(TokenKind::TripleQuote), t_triple_quote)),
+    // a raw string keeps its text as written, backslashes included
+    tuple((t_raw_quote, string_contents(TokenKind::Quote), t_quote)),
+  ))(input)?;
+  let value: Vec<u8> = string.into_iter()
+                               .flat_map(|token| token.lexeme)
+                               .collect();
+  Ok((input, Node::String{ value: String::from_utf8(value).unwrap() }))
+}
+
+// The span of the tokens consumed between `before` and `after`, which must not be the same input.
+fn consumed_span(before: &Tokens, after: &Tokens) -> Span {
+  let consumed = before.len() - after.len();
+  Span{start: before.tokens[0].start_offset, end: before.tokens[consumed - 1].end_offset}
+}
+
+// Runs `parser` and also returns the span of the tokens it consumed.
+fn with_span(parser: impl Fn(Tokens) -> IResult<Tokens, Node>) -> impl Fn(Tokens) -> IResult<Tokens, (Node, Span)> {
+  move |input: Tokens| {
+    let (rest, node) = parser(input.clone())?;
+    let span = consumed_span(&input, &rest);
+    Ok((rest, (node, span)))
+  }
+}
+
+// Folds `first {operator operand}` to the left. Every step is a MathExpression spanning from the
+// first operand to the end of its right operand.
+fn fold_binary(first: (Node, Span), rest: Vec<(&'static [u8], (Node, Span))>) -> Node {
+  let (result, _) = rest.into_iter().fold(first, |(leftside, left_span), (name, (rightside, right_span))| {
+    let span = Span{start: left_span.start, end: right_span.end};
+    let node = Node::MathExpression{name: name.to_vec(), children: vec![leftside, rightside]};
+    (Node::Spanned{span, children: vec![node]}, span)
+  });
+  result
+}
+
+pub fn function_call(input: Tokens) -> IResult<Tokens, Node> {
+  let start = input.clone();
+  let (input, fxn_name) = identifier(input)?;
+  let (input, _) = (t_left_paren)(input)?;
+  // the children of a call are its argument expressions
+  let (input, args) = opt(call_arguments)(input)?;
+  let args = args.unwrap_or_default();
+  let (input, _) = (t_right_paren)(input)?;
+  let name: Vec<u8> = match fxn_name {
+    Node::Identifier{value} => value,
+    _ => unreachable!(),
+  }; 
+  let span = consumed_span(&start, &input);
+  Ok((input, Node::Spanned{span, children: vec![Node::FunctionCall{name, children: args}]}))
+}
+
+// A call or an index expression is tried before a plain identifier, so `n * fact(n - 1)` and
+// `xs[0] + 1` are arithmetic on their values. A paren can only start a parenthesized expression,
+// which is parsed without trying the others, so each level of nested parens takes little stack.
+pub fn value(input: Tokens) -> IResult<Tokens, Node> {
+  match input.tokens.first() {
+    Some(token) if token.kind == TokenKind::LeftParen => paren_expression(input),
+    _ => alt((number, index_expression, function_call, identifier, boolean, negation, unary_plus))(input),
+  }
+}
+
+// "-" value. A negated number literal becomes a negative literal, anything else is negated when evaluated.
+pub fn negation(input: Tokens) -> IResult<Tokens, Node> {
+  let start = input.clone();
+  let (input, _) = t_dash(input)?;
+  if let Ok(result) = signed_number(input.clone(), true) {
+    return Ok(result);
+  }
+  let (input, operand) = value(input)?;
+  let span = consumed_span(&start, &input);
+  let node = Node::UnaryExpression{name: b"neg".to_vec(), children: vec![operand]};
+  Ok((input, Node::Spanned{span, children: vec![node]}))
+}
+
+// "+" value. A unary plus leaves a number as it is, so "+2" is the literal 2, anything else is checked to be a number when evaluated.
+pub fn unary_plus(input: Tokens) -> IResult<Tokens, Node> {
+  let start = input.clone();
+  let (input, _) = t_plus(input)?;
+  if let Ok(result) = signed_number(input.clone(), false) {
+    return Ok(result);
+  }
+  let (input, operand) = value(input)?;
+  let span = consumed_span(&start, &input);
+  let node = Node::UnaryExpression{name: b"pos".to_vec(), children: vec![operand]};
+  Ok((input, Node::Spanned{span, children: vec![node]}))
+}
+
+// Parses "(" expression ")". The opening paren is always consumed before recursing into
+// expression, so every cycle through expression -> math_expression -> value makes progress.
+pub fn paren_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_left_paren(input)?;
+  let (input, inner) = expression(input)?;
+  let (input, _) = t_right_paren(input)?;
+  Ok((input, inner))
+}
+
+pub fn math_operator(input: Tokens) -> IResult<Tokens, &'static [u8]> {
+  let (input, operator) = alt((t_plus, t_dash))(input)?;
+  let name: &'static [u8] = match operator.kind {
+    TokenKind::Plus => b"add",
+    TokenKind::Dash => b"sub",
+    _ => unreachable!(),
+  };
+  Ok((input, name))
+}
+
+pub fn term_operator(input: Tokens) -> IResult<Tokens, &'static [u8]> {
+  let (input, operator) = alt((t_star, t_slash))(input)?;
+  let name: &'static [u8] = match operator.kind {
+    TokenKind::Star => b"mul",
+    TokenKind::Slash => b"div",
+    _ => unreachable!(),
+  };
+  Ok((input, name))
+}
+
+// value {("*" | "/") value}, folded to the left. Multiplication and division bind tighter than
+// addition and subtraction.
+pub fn term(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(value)(input)?;
+  let (input, rest) = many0(pair(term_operator, with_span(value)))(input)?;
+  Ok((input, fold_binary(leftside, rest)))
+}
+
+pub fn math_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(term)(input)?;
+  let (input, rest) = many0(pair(math_operator, with_span(term)))(input)?;
+  if rest.is_empty() {
+    // A lone value is accepted here rather than failing, otherwise expression would parse it
+    // a second time through another alternative and nested parens would take exponential
+    // time.
+    return Ok((input, leftside.0));
+  }
+  Ok((input, fold_binary(leftside, rest)))
+}
+
+
+pub fn map_entry(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, key) = string(input)?;
+  let (input, _) = t_colon(input)?;
+  let (input, value) = expression(input)?;
+  Ok((input, Node::MapEntry{children: vec![key, value]}))
+}
+
+pub fn other_map_entry(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_comma(input)?;
+  map_entry(input)
+}
+
+pub fn map_literal(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_left_curly(input)?;
+  let (input, first) = opt(map_entry)(input)?;
+  let (input, entries) = match first {
+    Some(entry) => {
+      let (input, mut others) = many0(other_map_entry)(input)?;
+      let mut entries = vec![entry];
+      entries.append(&mut others);
+      (input, entries)
+    },
+    None => (input, vec![]),
+  };
+  let (input, _) = t_right_curly(input)?;
+  Ok((input, Node::Map{children: entries}))
+}
+
+// "[" [expression {"," expression}] "]"
+pub fn list_literal(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_left_bracket(input)?;
+  let (input, elements) = opt(arguments)(input)?;
+  let (input, _) = t_right_bracket(input)?;
+  let children = match elements {
+    Some(Node::FunctionArguments{children}) => children,
+    _ => vec![],
+  };
+  Ok((input, Node::List{children}))
+}
+
+pub fn index(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_left_bracket(input)?;
+  let (input, key) = expression(input)?;
+  let (input, _) = t_right_bracket(input)?;
+  Ok((input, key))
+}
+
+pub fn index_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, (target, target_span)) = with_span(|input| alt((function_call, map_literal, identifier))(input))(input)?;
+  let (input, keys) = many1(with_span(index))(input)?;
+  let (result, _) = keys.into_iter().fold((target, target_span), |(target, target_span), (key, key_span)| {
+    let span = Span{start: target_span.start, end: key_span.end};
+    (Node::Spanned{span, children: vec![Node::Index{children: vec![target, key]}]}, span)
+  });
+  Ok((input, result))
+}
+
+pub fn operand(input: Tokens) -> IResult<Tokens, Node> {
+  alt((boolean, lambda, while_expression, loop_expression, match_expression, math_expression, index_expression, function_call, map_literal, list_literal, number, string,identifier))(input)
+}
+
+pub fn equality_operator(input: Tokens) -> IResult<Tokens, &'static [u8]> {
+  let (input, operator) = alt((t_equal_equal, t_not_equal))(input)?;
+  let name: &'static [u8] = match operator.kind {
+    TokenKind::EqualEqual => b"eq",
+    TokenKind::NotEqual => b"ne",
+    _ => unreachable!(),
+  };
+  Ok((input, name))
+}
+
+pub fn shift_operator(input: Tokens) -> IResult<Tokens, &'static [u8]> {
+  let (input, operator) = alt((t_shift_left, t_shift_right))(input)?;
+  let name: &'static [u8] = match operator.kind {
+    TokenKind::ShiftLeft => b"shl",
+    TokenKind::ShiftRight => b"shr",
+    _ => unreachable!(),
+  };
+  Ok((input, name))
+}
+
+// operand {("<<" | ">>") operand}, folded to the left. Shifts bind looser than + and -.
+pub fn shift_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(operand)(input)?;
+  let (input, rest) = many0(pair(shift_operator, with_span(operand)))(input)?;
+  Ok((input, fold_binary(leftside, rest)))
+}
+
+// shift_expression {"&" shift_expression}, folded to the left.
+pub fn bit_and_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(shift_expression)(input)?;
+  let (input, rest) = many0(pair(t_ampersand, with_span(shift_expression)))(input)?;
+  let rest = rest.into_iter().map(|(_, rightside)| (&b"bitand"[..], rightside)).collect();
+  Ok((input, fold_binary(leftside, rest)))
+}
+
+// bit_and_expression {"^" bit_and_expression}, folded to the left.
+pub fn bit_xor_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(bit_and_expression)(input)?;
+  let (input, rest) = many0(pair(t_caret, with_span(bit_and_expression)))(input)?;
+  let rest = rest.into_iter().map(|(_, rightside)| (&b"bitxor"[..], rightside)).collect();
+  Ok((input, fold_binary(leftside, rest)))
+}
+
+// bit_xor_expression {"|" bit_xor_expression}, folded to the left. The bitwise operators bind
+// tighter than comparisons, so `x & 1 == 1` tests the low bit.
+pub fn bit_or_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(bit_xor_expression)(input)?;
+  let (input, rest) = many0(pair(t_pipe, with_span(bit_xor_expression)))(input)?;
+  let rest = rest.into_iter().map(|(_, rightside)| (&b"bitor"[..], rightside)).collect();
+  Ok((input, fold_binary(leftside, rest)))
+}
+
+pub fn comparison_operator(input: Tokens) -> IResult<Tokens, &'static [u8]> {
+  let (input, operator) = alt((t_less, t_greater, t_less_equal, t_greater_equal))(input)?;
+  let name: &'static [u8] = match operator.kind {
+    TokenKind::Less => b"lt",
+    TokenKind::Greater => b"gt",
+    TokenKind::LessEqual => b"le",
+    TokenKind::GreaterEqual => b"ge",
+    _ => unreachable!(),
+  };
+  Ok((input, name))
+}
+
+// A bitwise expression optionally compared to a second one. Without an operator the left side
+// itself is returned, so it is never parsed twice.
+pub fn comparison_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(bit_or_expression)(input)?;
+  let (input, rest) = opt(pair(comparison_operator, with_span(bit_or_expression)))(input)?;
+  Ok((input, fold_binary(leftside, rest.into_iter().collect())))
+}
+
+// Like comparison_expression, one level up: `a < b == c < d` compares the two comparisons.
+pub fn equality_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(comparison_expression)(input)?;
+  let (input, rest) = opt(pair(equality_operator, with_span(comparison_expression)))(input)?;
+  Ok((input, fold_binary(leftside, rest.into_iter().collect())))
+}
+
+// equality_expression {"&&" equality_expression}, folded to the left.
+pub fn and_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(equality_expression)(input)?;
+  let (input, rest) = many0(pair(t_and_and, with_span(equality_expression)))(input)?;
+  let rest = rest.into_iter().map(|(_, rightside)| (&b"and"[..], rightside)).collect();
+  Ok((input, fold_binary(leftside, rest)))
+}
+
+// and_expression {"||" and_expression}, folded to the left. `&&` binds tighter than `||`.
+pub fn or_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, leftside) = with_span(and_expression)(input)?;
+  let (input, rest) = many0(pair(t_or_or, with_span(and_expression)))(input)?;
+  let rest = rest.into_iter().map(|(_, rightside)| (&b"or"[..], rightside)).collect();
+  Ok((input, fold_binary(leftside, rest)))
+}
+
+fn pipe_callee(input: Tokens) -> IResult<Tokens, Node> {
+  alt((function_call, identifier))(input)
+}
+
+// The call a pipe makes, with the piped value before the callee's own arguments. None when the
+// callee is not a function name or call.
+fn pipe_into(piped: Node, callee: Node) -> Option<Node> {
+  match callee {
+    Node::Identifier{value} => Some(Node::FunctionCall{name: value, children: vec![Node::Expression{children: vec![piped]}]}),
+    Node::FunctionCall{name, children: mut args} => {
+      args.insert(0, Node::Expression{children: vec![piped]});
+      Some(Node::FunctionCall{name, children: args})
+    },
+    Node::Spanned{mut children, ..} if children.len() == 1 => pipe_into(piped, children.remove(0)),
+    _ => None,
+  }
+}
+
+pub fn expression(input: Tokens) -> IResult<Tokens, Node> {
+   let (input, result) = or_expression(input)?;
+   pipes(input, result)
+}
+
+// The {"|>" (function_call | identifier)} that may follow an expression, folded to the left.
+// `x |> f` is the call f(x) and `x |> f(y)` is f(x, y), so the pipe binds looser than every other
+// operator. It is not a level of the precedence chain so parenthesized expressions nest no deeper.
+fn pipes(mut input: Tokens, mut result: Node) -> IResult<Tokens, Node> {
+  while let Ok((rest, (_, (callee, callee_span)))) = pair(t_pipe_forward, with_span(pipe_callee))(input.clone()) {
+    // the call spans from its piped value, when that has a span, to the end of the callee
+    let span = match &result {
+      Node::Spanned{span, ..} => Some(Span{start: span.start, end: callee_span.end}),
+      _ => None,
+    };
+    let call = match pipe_into(result, callee) {
+      Some(call) => call,
+      None => return fail(input),
+    };
+    result = match span {
+      Some(span) => Node::Spanned{span, children: vec![call]},
+      None => call,
+    };
+    input = rest;
+  }
+  Ok((input, Node::Expression{children: vec! [result]}))
+}
+
+pub fn statement(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, result) = alt((variable_define, const_define, assignment, expression_statement, function_return, loop_break))(input)?;
+  let (input, _) = (t_semicolon)(input)?;
+  let (input, _) = opt(comment)(input)?;
+  Ok((input, result))
+}
+
+pub fn function_return(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_return(input)?;
+  let (input, result) = alt((expression, identifier))(input)?;
+  Ok((input, Node::FunctionReturn{children: vec! [result]}))
+}
+
+// "break" [expression], leaving the innermost loop with the value of the expression, or null.
+pub fn loop_break(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_break(input)?;
+  let (input, value) = opt(expression)(input)?;
+  Ok((input, Node::Break{children: value.into_iter().collect()}))
+}
+
+pub fn variable_define(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_let(input)?;
+  if let Ok((input, _)) = t_rec(input.clone()) {
+    return recursive_define(input);
+  }
+  if let Ok((input, _)) = t_left_bracket(input.clone()) {
+    return destructure(input);
+  }
+  let (input, variable) = identifier(input)?;
+  let (input, annotation) = opt(type_annotation)(input)?;
+  let (input, _) = (t_equal)(input)?;
+  let (input, expression) = expression(input)?;
+  let mut children = vec![variable,expression];
+  children.extend(annotation);
+  Ok((input, Node::VariableDefine{children}))
+}
+
+// "const" identifier [type_annotation] "=" expression
+pub fn const_define(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_const(input)?;
+  let (input, variable) = identifier(input)?;
+  let (input, annotation) = opt(type_annotation)(input)?;
+  let (input, _) = (t_equal)(input)?;
+  let (input, expression) = expression(input)?;
+  let mut children = vec![variable,expression];
+  children.extend(annotation);
+  Ok((input, Node::ConstDefine{children}))
+}
+
+// identifier {"," identifier} "]" "=" expression, after "let [".
+fn destructure(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, first) = identifier(input)?;
+  let (input, others) = many0(pair(t_comma, identifier))(input)?;
+  let (input, _) = t_right_bracket(input)?;
+  let (input, _) = t_equal(input)?;
+  let (input, value) = expression(input)?;
+  let mut children = vec![first];
+  children.extend(others.into_iter().map(|(_, name)| name));
+  children.push(value);
+  Ok((input, Node::Destructure{children}))
+}
+
+// identifier "=" expression, after "let rec".
+fn recursive_define(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, variable) = identifier(input)?;
+  let (input, _) = t_equal(input)?;
+  let (input, expression) = expression(input)?;
+  Ok((input, Node::RecursiveDefine{children: vec![variable, expression]}))
+}
+
+// ":" identifier, the type a variable or parameter is declared with.
+pub fn type_annotation(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_colon(input)?;
+  let (input, name) = identifier(input)?;
+  match name {
+    Node::Identifier{value} => Ok((input, Node::TypeAnnotation{value})),
+    _ => unreachable!(),
+  }
+}
+
+// identifier ":" type, an annotated function parameter.
+pub fn typed_parameter(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, name) = identifier(input)?;
+  let (input, annotation) = type_annotation(input)?;
+  Ok((input, Node::Parameter{children: vec![name, annotation]}))
+}
+
+pub fn parameters(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, first) = alt((typed_parameter, expression))(input)?;
+  let (input, mut others) = many0(other_parameter)(input)?;
+  let mut params = vec![first];
+  params.append(&mut others);
+  Ok((input, Node::FunctionArguments{children: params}))
+}
+
+pub fn other_parameter(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_comma(input)?;
+  alt((typed_parameter, expression))(input)
+}
+
+// An expression, or one followed by "=" expression. That is an assignment to something other than
+// a variable, like `5 = x`, which is kept as an Assignment so the interpreter reports the invalid
+// target at its position, before running the program, rather than failing to parse.
+fn expression_statement(input: Tokens) -> IResult<Tokens, Node> {
+  let start = input.clone();
+  let (input, target) = expression(input)?;
+  match pair(t_equal, expression)(input.clone()) {
+    Ok((input, (_, value))) => {
+      let span = consumed_span(&start, &input);
+      Ok((input, Node::Spanned{span, children: vec![Node::Assignment{children: vec![target, value]}]}))
+    },
+    Err(_) => Ok((input, target)),
+  }
+}
+
+// identifier "=" expression, giving an existing variable a new value. identifier "+=" expression
+// is the assignment of identifier + expression.
+pub fn assignment(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, variable) = identifier(input)?;
+  if let Ok((input, _)) = t_plus_equal(input.clone()) {
+    let (input, expression) = expression(input)?;
+    let sum = Node::MathExpression{name: b"add".to_vec(), children: vec![variable.clone(), expression]};
+    return Ok((input, Node::Assignment{children: vec![variable, Node::Expression{children: vec![sum]}]}));
+  }
+  let (input, _) = t_equal(input)?;
+  let (input, expression) = expression(input)?;
+  Ok((input, Node::Assignment{children: vec![variable, expression]}))
+}
+
+pub fn arguments(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, arg) = expression(input)?;
+  let (input, mut others) = many0(other_arg) (input)?;
+  let mut args = vec! [arg];
+  args.append (&mut others) ;
+  Ok((input, Node::FunctionArguments{children: args}))
+}
+
+
+pub fn other_arg(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_comma(input)?;
+  expression(input)
+}
+
+// identifier "=" expression, an argument bound to the parameter of that name.
+pub fn named_argument(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, name) = identifier(input)?;
+  let (input, _) = t_equal(input)?;
+  let (input, value) = expression(input)?;
+  Ok((input, Node::NamedArgument{children: vec![name, value]}))
+}
+
+pub fn call_argument(input: Tokens) -> IResult<Tokens, Node> {
+  alt((named_argument, expression))(input)
+}
+
+// The arguments of a call, which unlike parameters may be named.
+pub fn call_arguments(input: Tokens) -> IResult<Tokens, Vec<Node>> {
+  let (input, arg) = call_argument(input)?;
+  let (input, others) = many0(pair(t_comma, call_argument))(input)?;
+  let mut args = vec![arg];
+  args.extend(others.into_iter().map(|(_, arg)| arg));
+  Ok((input, args))
+}
+
+pub fn function_define(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, doc) = many0(doc_comment_line)(input)?;
+  let (input, _) = t_fn(input)?;
+  let (input, fxn_name) = identifier(input)?;
+  let name = match fxn_name {
+    Node::Identifier{value} => value,
+    _ => unreachable!(),
+  };
+  let (input, (fxn_arguments, fxn_statements)) = function_parts(input)?;
+  let mut children = vec![fxn_arguments,fxn_statements];
+  if !doc.is_empty() {
+    children.push(Node::DocComment{value: doc.join("\n")});
+  }
+  Ok((input, Node::FunctionDefine{name, children}))
+}
+
+// "(" [parameters] ")" "{" body "}", the parameters and statements of a function.
+fn function_parts(input: Tokens) -> IResult<Tokens, (Node, Node)> {
+  let (input, _) = t_left_paren(input)?;
+  let (input, args) = many0(parameters)(input)?;
+  let (input, _) = t_right_paren(input)?;
+  let (input, _) = t_left_curly(input)?;
+  let (input, mut statements) = many0(alt((if_expression, while_expression, loop_expression, statement)))(input)?;
+  // a last expression without a semicolon is the function's value, as if it were returned
+  let (input, tail) = opt(expression)(input)?;
+  statements.extend(tail);
+  let (input, _) = t_right_curly(input)?;
+  let fxn_statements = Node::FunctionStatements{children: statements};
+  let fxn_arguments = if args.is_empty() {
+    Node::FunctionArguments{children: vec![]}
+  } else {
+    args[0].clone()
+  };
+  Ok((input, (fxn_arguments, fxn_statements)))
+}
+
+// "fn" "(" [parameters] ")" "{" body "}", an anonymous function used as a value.
+pub fn lambda(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_fn(input)?;
+  let (input, (arguments, statements)) = function_parts(input)?;
+  Ok((input, Node::Lambda{children: vec![arguments, statements]}))
+}
+
+// (expression | "_") "=>" expression
+pub fn match_arm(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, pattern) = match pair(t_underscore, t_fat_arrow)(input.clone()) {
+    Ok((input, _)) => (input, Node::Wildcard),
+    Err(_) => {
+      let (input, (pattern, _)) = pair(expression, t_fat_arrow)(input)?;
+      (input, pattern)
+    },
+  };
+  let (input, body) = expression(input)?;
+  Ok((input, Node::MatchArm{children: vec![pattern, body]}))
+}
+
+// "match" expression "{" match_arm {"," match_arm} [","] "}". The first arm whose pattern equals
+// the value is evaluated.
+pub fn match_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_match(input)?;
+  let (input, scrutinee) = expression(input)?;
+  let (input, _) = t_left_curly(input)?;
+  let (input, first) = match_arm(input)?;
+  let (input, others) = many0(pair(t_comma, match_arm))(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  let (input, _) = t_right_curly(input)?;
+  let mut children = vec![scrutinee, first];
+  children.extend(others.into_iter().map(|(_, arm)| arm));
+  Ok((input, Node::Match{children}))
+}
+
+// "{" {statement} "}", the body of an if, else or while.
+pub fn block(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_left_curly(input)?;
+  let (input, mut statements) = many0(alt((if_expression, while_expression, loop_expression, block_statement)))(input)?;
+  // a last expression without a semicolon is the block's value
+  let (input, tail) = opt(expression)(input)?;
+  statements.extend(tail);
+  let (input, _) = t_right_curly(input)?;
+  Ok((input, Node::Block{children: statements}))
+}
+
+// A statement in a block, kept in a Statement node so it can be told apart from a trailing expression.
+fn block_statement(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, result) = statement(input)?;
+  Ok((input, Node::Statement{children: vec![result]}))
+}
+
+// "while" expression block. A loop is also an expression, its value is that of the last run of
+// its body. "while" "let" identifier "=" expression block is a while let loop.
+pub fn while_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_while(input)?;
+  if let Ok((input, _)) = t_let(input.clone()) {
+    let (input, name) = identifier(input)?;
+    let (input, _) = t_equal(input)?;
+    let (input, value) = expression(input)?;
+    let (input, body) = block(input)?;
+    return Ok((input, Node::WhileLet{children: vec![name, value, body]}));
+  }
+  let (input, condition) = expression(input)?;
+  let (input, body) = block(input)?;
+  Ok((input, Node::While{children: vec![condition, body]}))
+}
+
+// "loop" block, running the block until a break in it leaves the loop. Its value is that of the
+// break.
+pub fn loop_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_loop(input)?;
+  let (input, body) = block(input)?;
+  Ok((input, Node::Loop{children: vec![body]}))
+}
+
+// "if" expression block ["else" (if_expression | block)]. The condition is a full expression,
+// so it needs no parentheses. An `else if` chain nests an If as the else branch.
+pub fn if_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = t_if(input)?;
+  let (input, condition) = expression(input)?;
+  let (input, then_branch) = block(input)?;
+  let (input, else_branch) = opt(pair(t_else, alt((if_expression, block))))(input)?;
+  let mut children = vec![condition, then_branch];
+  if let Some((_, else_branch)) = else_branch {
+    children.push(else_branch);
+  }
+  Ok((input, Node::If{children}))
+}
+
+pub fn comment(input: Tokens) -> IResult<Tokens, Node> {
+  let mut comment_text = Vec::new();
+  let (input, first) = t_slash(input)?;
+  let (input, _) = t_slash(input)?;
+  // a line comment runs until the end of the line it starts on, whichever line ending was used
+  let line = first.start_line;
+  let (input, comment_tokens) = many0(check_token(&|tk| tk.kind != TokenKind::EOF && tk.start_line == line))(input)?;
+  for token in comment_tokens {
+    comment_text.extend_from_slice(&token.lexeme);
+  }
+  Ok((input, Node::Comment{ value: comment_text }))
+}
+
+// "///" and the rest of its line, a line of documentation for the function that follows. The
+// lexer drops whitespace, so the spaces between the words are restored from the token offsets.
+fn doc_comment_line(input: Tokens) -> IResult<Tokens, String> {
+  let (input, (_, _, third)) = tuple((t_slash, t_slash, t_slash))(input)?;
+  let line = third.start_line;
+  let (input, comment_tokens) = many0(check_token(&|tk| tk.kind != TokenKind::EOF && tk.start_line == line))(input)?;
+  let mut text = Vec::new();
+  let mut end = third.end_offset;
+  for token in comment_tokens {
+    text.resize(text.len() + token.start_offset - end, b' ');
+    text.extend_from_slice(&token.lexeme);
+    end = token.end_offset;
+  }
+  Ok((input, String::from_utf8_lossy(&text).trim().to_string()))
+}
+
+// A top-level expression may end with a semicolon or not, so `123` and `123;` are the same program.
+pub fn top_level_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, result) = expression(input)?;
+  let (input, _) = opt(t_semicolon)(input)?;
+  Ok((input, result))
+}
+
+pub fn program(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, result) = many1(alt((function_define,comment,if_expression,statement,top_level_expression,string,boolean,number)))(input)?;
+  Ok((input, Node::Program{ children: result }))
+}
+// Parses as much of the source as possible. Returns the program made of the top-level items that
+// parsed, if any did, and the tokens from the first one that could not be parsed onwards. The
+// end of input marker is not part of the leftovers, so they are empty when everything parsed.
+pub fn parse_partial(source: &str) -> (Option<Node>, Vec<Token>) {
+  let tokens = lex(source);
+  let (tree, rest) = match program(tokens.clone()) {
+    Ok((rest, tree)) => (Some(tree), rest),
+    Err(_) => (None, tokens),
+  };
+  let leftovers = rest.tokens.into_iter().filter(|token| token.kind != TokenKind::EOF).collect();
+  (tree, leftovers)
+}
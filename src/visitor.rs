@@ -0,0 +1,39 @@
+use crate::parser::Node;
+
+// A traversal over the AST. Every method defaults to visiting the node's children, so an
+// implementer only overrides the node kinds it cares about, and calls walk() from an override
+// to keep descending.
+pub trait Visitor {
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::FunctionDefine { .. } => self.visit_function_define(node),
+            Node::FunctionCall { .. } => self.visit_function_call(node),
+            Node::VariableDefine { .. } | Node::ConstDefine { .. } => self.visit_variable_define(node),
+            Node::Identifier { .. } => self.visit_identifier(node),
+            _ => walk(self, node),
+        }
+    }
+
+    fn visit_function_define(&mut self, node: &Node) {
+        walk(self, node)
+    }
+
+    fn visit_function_call(&mut self, node: &Node) {
+        walk(self, node)
+    }
+
+    fn visit_variable_define(&mut self, node: &Node) {
+        walk(self, node)
+    }
+
+    fn visit_identifier(&mut self, node: &Node) {
+        walk(self, node)
+    }
+}
+
+// Visits each child of the node in order.
+pub fn walk<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    for child in node.children() {
+        visitor.visit_node(child);
+    }
+}
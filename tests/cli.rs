@@ -0,0 +1,30 @@
+use std::process::Command;
+
+// Writes a program to a file of its own and runs the asalang binary on it.
+fn run(name: &str, source: &str) -> std::process::Output {
+  let path = std::env::temp_dir().join(format!("asalang_cli_{}_{}.asa", name, std::process::id()));
+  std::fs::write(&path, source).unwrap();
+  let output = Command::new(env!("CARGO_BIN_EXE_asalang")).arg(&path).output().unwrap();
+  std::fs::remove_file(&path).unwrap();
+  output
+}
+
+#[test]
+fn cli_prints_main_result() {
+  let output = run("ok", r#"fn main(){return add(1, 2);} fn add(a, b){return a + b;}"#);
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+}
+
+#[test]
+fn cli_reports_errors() {
+  let output = run("err", r#"fn main(){return nope();}"#);
+  assert!(!output.status.success());
+  assert_eq!(String::from_utf8_lossy(&output.stderr), "Error: undefined function nope\n");
+}
+
+#[test]
+fn cli_requires_a_path() {
+  let output = Command::new(env!("CARGO_BIN_EXE_asalang")).output().unwrap();
+  assert!(!output.status.success());
+}
@@ -3,7 +3,6 @@ extern crate nom;
 use std::io::Write;
 
 use asalang::*;
-use nom::IResult;
 
 macro_rules! test_fragment {
   ($func:ident, $test:tt, $expected:expr) => (
@@ -15,7 +14,7 @@ macro_rules! test_fragment {
           assert_eq!(tokens.is_done(), true); // Check that input token stream is fully parsed
           let mut interpreter = Interpreter::new();
           let result = interpreter.exec(&tree);
-          std::io::stdout().flush();
+          std::io::stdout().flush().unwrap();
           assert_eq!(result, $expected);
           Ok(())
         },
@@ -34,7 +33,7 @@ macro_rules! test_program {
         Ok((tokens, tree)) => {
           assert_eq!(tokens.is_done(), true); // Check that input token stream is fully parsed
           let mut interpreter = Interpreter::new();
-          let compile_result = interpreter.exec(&tree)?;
+          interpreter.exec(&tree)?;
           let main_result = interpreter.start_main(vec![]);
           assert_eq!(main_result, $expected);
           Ok(())
@@ -50,10 +49,10 @@ test_fragment!(interpreter_numeric, r#"123"#, Ok(Value::Number(123)));
 test_fragment!(interpreter_string, r#""helloworld""#, Ok(Value::String("helloworld".to_string())));
 test_fragment!(interpreter_bool_true, r#"true"#, Ok(Value::Bool(true)));
 test_fragment!(interpreter_bool_false, r#"false"#, Ok(Value::Bool(false)));
-test_fragment!(interpreter_identifier, r#"x"#, Err(AsaErrorKind::UndefinedFunction));
-test_fragment!(interpreter_function_call, r#"foo()"#, Err(AsaErrorKind::UndefinedFunction));
-test_fragment!(interpreter_function_call_one_arg, r#"foo(a)"#, Err(AsaErrorKind::UndefinedFunction));
-test_fragment!(interpreter_function_call_more_args, r#"foo(a,b,c)"#, Err(AsaErrorKind::UndefinedFunction));
+test_fragment!(interpreter_identifier, r#"x"#, Err(AsaErrorKind::VariableNotDefined("x".to_string())));
+test_fragment!(interpreter_function_call, r#"foo()"#, Err(AsaErrorKind::UndefinedFunction("foo".to_string())));
+test_fragment!(interpreter_function_call_one_arg, r#"foo(a)"#, Err(AsaErrorKind::UndefinedFunction("foo".to_string())));
+test_fragment!(interpreter_function_call_more_args, r#"foo(a,b,c)"#, Err(AsaErrorKind::UndefinedFunction("foo".to_string())));
 test_fragment!(interpreter_variable_define, r#"let x = 123;"#, Ok(Value::Number(123)));
 test_fragment!(interpreter_variable_init, r#"let x = 1;"#, Ok(Value::Number(1)));
 test_fragment!(interpreter_variable_bool, r#"let bool = true;"#, Ok(Value::Bool(true)));
@@ -63,8 +62,8 @@ test_fragment!(interpreter_math, r#"1 + 1"#, Ok(Value::Number(2)));
 test_fragment!(interpreter_math_no_space, r#"1-1"#, Ok(Value::Number(0)));
 test_fragment!(interpreter_math_multiply, r#"2 + 4"#, Ok(Value::Number(6)));
 test_fragment!(interpreter_assign_math, r#"let x = 1 + 1;"#, Ok(Value::Number(2)));
-test_fragment!(interpreter_assign_function, r#"let x = foo();"#, Err(AsaErrorKind::UndefinedFunction));
-test_fragment!(interpreter_assign_function_arguments, r#"let x = foo(a,b,c);"#, Err(AsaErrorKind::UndefinedFunction));
+test_fragment!(interpreter_assign_function, r#"let x = foo();"#, Err(AsaErrorKind::UndefinedFunction("foo".to_string())));
+test_fragment!(interpreter_assign_function_arguments, r#"let x = foo(a,b,c);"#, Err(AsaErrorKind::UndefinedFunction("foo".to_string())));
 
 // Test full programs
 test_program!(interpreter_define_function, r#"fn main(){return foo();} fn foo(){return 5;}"#, Ok(Value::Number(5)));
@@ -95,5 +94,986 @@ fn main() {
 test_fragment!(samir_interpreter_identifier_redefinition, r#"let x = 5; let x = x + 1;"#, Ok(Value::Number(6)));
 test_fragment!(samir_interpreter_math_subtract, r#"5 - 3"#, Ok(Value::Number(2)));
 test_fragment!(samir_interpreter_assign_math_spaces, r#"let    x    =    1    +   1;"#, Ok(Value::Number(2)));
-test_fragment!(samir_interpreter_alphanumeric, r#"hello123"#, Err(AsaErrorKind::UndefinedFunction));
+test_fragment!(samir_interpreter_alphanumeric, r#"hello123"#, Err(AsaErrorKind::VariableNotDefined("hello123".to_string())));
 test_fragment!(samir_interpreter_variable_false, r#"let bool = false;"#, Ok(Value::Bool(false)));
+test_program!(samir_interpreter_crlf_program, "// entry point\r\nfn main() {\r\n  let x = 5; // five\r\n  return x;\r\n}\r\n", Ok(Value::Number(5)));
+test_fragment!(samir_interpreter_crlf_comment, "// first line\r\n1 + 2 // second line\r\n", Ok(Value::Number(3)));
+
+// Builtins
+test_fragment!(builtin_repeat, r#"repeat("ab", 3)"#, Ok(Value::String("ababab".to_string())));
+test_fragment!(builtin_repeat_zero, r#"repeat("x", 0)"#, Ok(Value::String("".to_string())));
+test_fragment!(builtin_repeat_negative, r#"repeat("x", 0 - 1)"#, Err(AsaErrorKind::Generic("repeat count must not be negative, got -1".to_string())));
+test_program!(builtin_repeat_shadowed, r#"fn main(){return repeat(1,2);} fn repeat(a,b){return a+b;}"#, Ok(Value::Number(3)));
+
+// Loading several sources into one interpreter
+#[test]
+fn interpreter_load_sources() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn double(a){return a+a;}"#)?;
+  interpreter.load_source(r#"fn quad(a){let d = double(a); return double(d);} fn main(){return quad(3);}"#)?;
+  assert_eq!(interpreter.start_main(vec![]), Ok(Value::Number(12)));
+  Ok(())
+}
+
+#[test]
+fn interpreter_load_sources_duplicate() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn double(a){return a+a;}"#)?;
+  let result = interpreter.load_source(r#"fn double(a){return a;}"#);
+  assert_eq!(result, Err(AsaErrorKind::DuplicateFunction("double".to_string())));
+  Ok(())
+}
+
+#[test]
+fn interpreter_load_sources_parse_error() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn double(a){return a+a;}"#)?;
+  let result = interpreter.load_source("fn main(){\n  let x = 1 +;\n}");
+  assert_eq!(result, Err(AsaErrorKind::ParseError("Unexpected Semicolon token at line 2, column 14".to_string())));
+  assert_eq!(interpreter.call("double", vec![Value::Number(2)]), Ok(Value::Number(4)));
+  Ok(())
+}
+
+#[test]
+fn interpreter_value_hash() {
+  use std::collections::HashSet;
+  let mut set = HashSet::new();
+  set.insert(Value::Number(1));
+  set.insert(Value::String("one".to_string()));
+  set.insert(Value::Bool(true));
+  set.insert(Value::Number(1));
+  assert_eq!(set.len(), 3);
+  assert!(set.contains(&Value::Number(1)));
+  assert!(set.contains(&Value::String("one".to_string())));
+  assert!(!set.contains(&Value::Bool(false)));
+}
+
+// Maps
+test_fragment!(interpreter_map_literal, r#"{"a": 1, "b": 2}"#, Ok(Value::Map([("a".to_string(), Value::Number(1)), ("b".to_string(), Value::Number(2))].into_iter().collect())));
+test_fragment!(interpreter_map_empty, r#"{}"#, Ok(Value::Map(Default::default())));
+test_fragment!(interpreter_map_index, r#"let m = {"a": 1, "b": 2}; m["b"]"#, Ok(Value::Number(2)));
+test_fragment!(interpreter_map_index_literal, r#"{"a": "x"}["a"]"#, Ok(Value::String("x".to_string())));
+test_fragment!(interpreter_map_key_not_found, r#"let m = {"a": 1}; m["z"]"#, Err(AsaErrorKind::KeyNotFound("z".to_string())));
+test_program!(interpreter_map_in_function, r#"fn main(){let m = {"x": 40}; return m["x"] ;} "#, Ok(Value::Number(40)));
+
+// Parenthesized expressions
+test_fragment!(interpreter_nested_parens, r#"((((1))))"#, Ok(Value::Number(1)));
+test_fragment!(interpreter_parens_math, r#"((1 + 2)) + (3 - (4 - 5))"#, Ok(Value::Number(7)));
+test_fragment!(interpreter_math_chain, r#"1 + 2 - 3 + 4"#, Ok(Value::Number(4)));
+
+#[test]
+fn interpreter_repeated_variable_reads() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.exec(&program(lex("let x = 3;")).unwrap().1)?;
+  let read = Node::Identifier{value: b"x".to_vec()};
+  for _ in 0..10_000 {
+    assert_eq!(interpreter.exec(&read), Ok(Value::Number(3)));
+  }
+  let source = format!("let y = x{};", " + x".repeat(99));
+  assert_eq!(interpreter.exec(&program(lex(&source)).unwrap().1), Ok(Value::Number(300)));
+  Ok(())
+}
+
+#[test]
+fn builtin_input() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_input(Box::new(std::io::Cursor::new("hello\r\nworld\n")));
+  let tree = program(lex(r#"let a = input(); let b = input("next"); b"#)).unwrap().1;
+  assert_eq!(interpreter.exec(&tree), Ok(Value::String("world".to_string())));
+  Ok(())
+}
+
+#[test]
+fn builtin_input_sandboxed() {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_input(Box::new(std::io::Cursor::new("hello\n")));
+  interpreter.set_sandbox(true);
+  let tree = program(lex(r#"input()"#)).unwrap().1;
+  assert_eq!(interpreter.exec(&tree), Err(AsaErrorKind::Forbidden("input".to_string())));
+}
+
+#[test]
+fn builtin_getenv() {
+  // reads a variable the environment already has, setting one would race with the other tests
+  let path = std::env::var("PATH").map_or(Value::Null, Value::String);
+  let mut interpreter = Interpreter::new();
+  let tree = program(lex(r#"getenv("PATH")"#)).unwrap().1;
+  assert_eq!(interpreter.exec(&tree), Ok(path));
+  interpreter.set_sandbox(true);
+  assert_eq!(interpreter.exec(&tree), Err(AsaErrorKind::Forbidden("getenv".to_string())));
+}
+
+test_fragment!(builtin_getenv_unset, r#"getenv("ASALANG_TEST_GETENV_UNSET")"#, Ok(Value::Null));
+
+// Floats and equality. Numbers compare by value, so an integer equals the float with the same value.
+test_fragment!(interpreter_float, r#"1.5"#, Ok(Value::Float(1.5)));
+test_fragment!(interpreter_float_math, r#"1.5 + 2"#, Ok(Value::Float(3.5)));
+test_fragment!(interpreter_equal_int_float, r#"1 == 1.0"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_equal_floats, r#"1.0 == 1.0"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_equal_ints, r#"1 + 1 == 2"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_not_equal, r#"1 != 2"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_equal_mixed_types, r#"1 == "1""#, Ok(Value::Bool(false)));
+test_fragment!(interpreter_equal_maps, r#"{"a": 1} == {"a": 1.0}"#, Ok(Value::Bool(true)));
+
+#[test]
+fn interpreter_expression_statement() {
+  let mut interpreter = Interpreter::new();
+  let statement = Node::Statement{children: vec![
+    Node::Expression{children: vec![Node::Number{value: 7}]}
+  ]};
+  assert_eq!(interpreter.exec(&statement), Ok(Value::Number(7)));
+}
+
+#[test]
+fn interpreter_invalid_statement_names_kind() {
+  let mut interpreter = Interpreter::new();
+  let statement = Node::Statement{children: vec![Node::Comment{value: vec![]}]};
+  assert_eq!(interpreter.exec(&statement), Err(AsaErrorKind::Generic("A Comment node cannot be used as a statement".to_string())));
+}
+test_fragment!(builtin_keys, r#"keys({"b": 2, "a": 1, "c": 3})"#, Ok(Value::List(vec![Value::String("a".to_string()), Value::String("b".to_string()), Value::String("c".to_string())])));
+test_fragment!(builtin_values, r#"values({"b": 2, "a": 1, "c": 3})"#, Ok(Value::List(vec![Value::Number(1), Value::Number(2), Value::Number(3)])));
+test_fragment!(builtin_keys_empty, r#"keys({})"#, Ok(Value::List(vec![])));
+test_fragment!(builtin_keys_not_map, r#"keys(1)"#, Err(AsaErrorKind::TypeMismatch("keys expects a map".to_string())));
+test_fragment!(builtin_error, r#"error("bad")"#, Err(AsaErrorKind::UserError("bad".to_string())));
+test_program!(builtin_error_stops_execution, r#"fn main(){let x = check(); return x;} fn check(){let a = error("bad"); return 1;}"#, Err(AsaErrorKind::UserError("bad".to_string())));
+test_fragment!(builtin_error_not_string, r#"error(1)"#, Err(AsaErrorKind::TypeMismatch("error expects a string message".to_string())));
+test_program!(interpreter_call_zero_args, r#"fn main(){return foo();} fn foo(){return 4;}"#, Ok(Value::Number(4)));
+test_program!(interpreter_call_one_arg, r#"fn main(){return foo(1);} fn foo(a){return a + 4;}"#, Ok(Value::Number(5)));
+test_program!(interpreter_call_two_args, r#"fn main(){return foo(10, 4);} fn foo(a, b){return a - b;}"#, Ok(Value::Number(6)));
+test_program!(interpreter_call_three_args, r#"fn main(){return foo(1, 2, 3);} fn foo(a, b, c){return a - b + c;}"#, Ok(Value::Number(2)));
+test_program!(interpreter_call_arity_mismatch, r#"fn main(){return foo();} fn foo(a){return a;}"#, Err(AsaErrorKind::ArityMismatch{expected: 1, found: 0}));
+test_program!(interpreter_undefined_function_name, r#"fn main(){let a = foo(); return nope();} fn foo(){return 1;}"#, Err(AsaErrorKind::UndefinedFunction("nope".to_string())));
+
+// String literals
+test_fragment!(interpreter_string_spaces, r#""let it be = true""#, Ok(Value::String("let it be = true".to_string())));
+test_fragment!(interpreter_triple_quoted_string, "\"\"\"first line\nsecond \"quoted\" line\"\"\"", Ok(Value::String("first line\nsecond \"quoted\" line".to_string())));
+
+#[test]
+fn interpreter_unterminated_triple_quoted_string() {
+  let mut interpreter = Interpreter::new();
+  let result = interpreter.load_source("fn main() {\n  return \"\"\"never closed;\n}");
+  assert_eq!(result, Err(AsaErrorKind::ParseError("Unterminated string starting at line 2, column 10".to_string())));
+}
+
+#[test]
+fn interpreter_quote_in_comment() {
+  assert_eq!(Interpreter::new().run_source("// say \"hi\nfn main(){return 1;}"), Ok(Value::Number(1)));
+  assert_eq!(Interpreter::new().run_source("fn main(){\n  let s = \"a\"; // \"\"\" b\n  return s;\n}"), Ok(Value::String("a".to_string())));
+}
+
+// Top-level expressions with and without a trailing semicolon
+test_fragment!(interpreter_numeric_semicolon, r#"123;"#, Ok(Value::Number(123)));
+test_fragment!(interpreter_expressions_semicolons, r#"1 + 1; 2 + 2"#, Ok(Value::Number(4)));
+test_fragment!(interpreter_expressions_trailing_semicolon, r#"1 + 1; 2 + 3;"#, Ok(Value::Number(5)));
+
+// Integer bounds. Results above i32::MAX overflow and results below i32::MIN underflow.
+test_fragment!(interpreter_min_literal, r#"-2147483648"#, Ok(Value::Number(i32::MIN)));
+test_fragment!(interpreter_negate_expression, r#"-(1 + 2)"#, Ok(Value::Number(-3)));
+test_fragment!(interpreter_sub_negative, r#"5 - -3"#, Ok(Value::Number(8)));
+test_fragment!(interpreter_add_overflow, r#"2147483647 + 1"#, Err(AsaErrorKind::NumberOverflow));
+test_fragment!(interpreter_sub_underflow, r#"-2147483648 - 1"#, Err(AsaErrorKind::NumberUnderflow));
+test_fragment!(interpreter_sub_min_overflow, r#"0 - -2147483648"#, Err(AsaErrorKind::NumberOverflow));
+test_fragment!(interpreter_negate_min_overflow, r#"let x = -2147483648; -x"#, Err(AsaErrorKind::NumberOverflow));
+test_fragment!(interpreter_negate_bool, r#"-true"#, Err(AsaErrorKind::TypeMismatch("Only numbers can be negated".to_string())));
+test_fragment!(builtin_starts_with, r#"starts_with("hello", "he")"#, Ok(Value::Bool(true)));
+test_fragment!(builtin_ends_with, r#"ends_with("hello", "lo")"#, Ok(Value::Bool(true)));
+test_fragment!(builtin_starts_with_false, r#"starts_with("hello", "lo")"#, Ok(Value::Bool(false)));
+test_fragment!(builtin_ends_with_not_string, r#"ends_with("hello", 1)"#, Err(AsaErrorKind::TypeMismatch("ends_with expects two strings".to_string())));
+test_fragment!(interpreter_underscore_variable, r#"let my_var = 2; my_var"#, Ok(Value::Number(2)));
+
+// Step budget
+#[test]
+fn interpreter_step_limit_stops_runaway_program() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn forever(n){return forever(n + 1);} fn main(){return forever(0);}"#)?;
+  interpreter.set_max_steps(200);
+  assert_eq!(interpreter.start_main(vec![]), Err(AsaErrorKind::StepLimitExceeded));
+  Ok(())
+}
+
+#[test]
+fn interpreter_step_limit_allows_small_program() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_max_steps(100);
+  interpreter.load_source(r#"fn main(){let x = 1 + 2; return x;}"#)?;
+  assert_eq!(interpreter.start_main(vec![]), Ok(Value::Number(3)));
+  assert!(interpreter.steps() <= 100);
+  Ok(())
+}
+
+#[test]
+fn interpreter_step_limit_stops_empty_loop() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn main(){while true {} return 0;}"#)?;
+  interpreter.set_max_steps(1000);
+  assert_eq!(interpreter.start_main(vec![]), Err(AsaErrorKind::StepLimitExceeded));
+  Ok(())
+}
+
+// Memory budget
+#[test]
+fn interpreter_memory_limit_stops_growing_string() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn main(){let s = "x"; while true { s = repeat(s, 2); } return s;}"#)?;
+  interpreter.set_max_memory(1_000_000);
+  assert_eq!(interpreter.start_main(vec![]), Err(AsaErrorKind::MemoryLimitExceeded));
+  // the string main held stops counting once its frame is gone
+  assert_eq!(interpreter.memory(), 0);
+  Ok(())
+}
+
+#[test]
+fn interpreter_memory_limit_stops_builtins_before_they_build() {
+  for source in [r#"repeat("x", 2147483647)"#, r#"range(0, 2147483647)"#, r#"zip(range(0, 20000), range(0, 20000))"#] {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_max_memory(1_000_000);
+    assert_eq!(interpreter.run_source(source), Err(AsaErrorKind::MemoryLimitExceeded), "{}", source);
+  }
+}
+
+#[test]
+fn interpreter_memory_limit_counts_only_live_values() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn main(){ let n = 0; while n < 20000 { let xs = [1, 2, 3]; n = n + 1; } return n; }"#)?;
+  interpreter.set_max_memory(1_000_000);
+  assert_eq!(interpreter.start_main(vec![]), Ok(Value::Number(20000)));
+  assert_eq!(interpreter.start_main(vec![]), Ok(Value::Number(20000)));
+  Ok(())
+}
+
+#[test]
+fn interpreter_memory_limit_allows_small_program() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_max_memory(10_000);
+  interpreter.load_source(r#"fn main(){let xs = append([1, 2], 3); return repeat("ab", 3);}"#)?;
+  assert_eq!(interpreter.start_main(vec![]), Ok(Value::String("ababab".to_string())));
+  Ok(())
+}
+
+// Conditionals, comparisons and logical operators
+test_program!(interpreter_if_compound_condition, r#"fn main(){return pick(3, 1);} fn pick(x, y){if x < 5 && y > 0 { return 1; } else { return 2; } return 3;}"#, Ok(Value::Number(1)));
+test_program!(interpreter_if_else_if, r#"fn main(){return sign(0 - 4);} fn sign(x){if x > 0 { return 1; } else if x == 0 { return 0; } else { return -1; }}"#, Ok(Value::Number(-1)));
+test_program!(interpreter_if_falls_through, r#"fn main(){if 1 >= 2 || "a" <= "b" && false { return 1; } return 2;}"#, Ok(Value::Number(2)));
+test_fragment!(interpreter_and_short_circuits, r#"false && nope()"#, Ok(Value::Bool(false)));
+test_fragment!(interpreter_compare_mixed_numbers, r#"1 < 1.5"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_compare_mismatch, r#"1 < "a""#, Err(AsaErrorKind::TypeMismatch("Only two numbers or two strings can be compared".to_string())));
+
+// Truthiness of conditions: 0, 0.0, "" and empty lists and maps are false
+test_program!(interpreter_truthy_zero, r#"fn main(){if 0 { return 1; } return 2;}"#, Ok(Value::Number(2)));
+test_program!(interpreter_truthy_number, r#"fn main(){if 5 { return 1; } return 2;}"#, Ok(Value::Number(1)));
+test_program!(interpreter_truthy_empty_string, r#"fn main(){if "" { return 1; } return 2;}"#, Ok(Value::Number(2)));
+test_program!(interpreter_truthy_string, r#"fn main(){if "x" { return 1; } return 2;}"#, Ok(Value::Number(1)));
+
+#[test]
+fn interpreter_strict_condition_not_bool() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_strict(true);
+  interpreter.load_source(r#"fn main(){if 1 { return 1; } return 2;}"#)?;
+  assert_eq!(interpreter.start_main(vec![]), Err(AsaErrorKind::TypeMismatch("A condition must be a boolean".to_string())));
+  Ok(())
+}
+
+// Redefining a variable in the same frame is allowed unless no_shadow is set
+test_fragment!(interpreter_redefinition_allowed, r#"let x = 5; let x = 6; x"#, Ok(Value::Number(6)));
+
+#[test]
+fn interpreter_no_shadow() {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_no_shadow(true);
+  assert_eq!(interpreter.load_source(r#"let x = 5; let x = 6;"#), Err(AsaErrorKind::Generic("variable already defined".to_string())));
+  assert_eq!(interpreter.run_source(r#"let y = 5; if true { let y = 6; y } else { y }"#), Ok(Value::Number(6)));
+}
+
+// Functions are registered before the program runs
+test_program!(interpreter_mutual_recursion, r#"fn main(){return is_even(10);} fn is_even(n){if n == 0 { return true; } return is_odd(n - 1);} fn is_odd(n){if n == 0 { return false; } return is_even(n - 1);}"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_forward_reference, r#"let y = twice(4); fn twice(x){return x + x;} y"#, Ok(Value::Number(8)));
+
+// List literals and equality of lists and maps
+test_fragment!(interpreter_list_equal, r#"[1,2] == [1,2]"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_list_order_matters, r#"[1,2] == [2,1]"#, Ok(Value::Bool(false)));
+test_fragment!(interpreter_map_equal, r#"{"a": [1], "b": 2} == {"b": 2.0, "a": [1]}"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_list_not_ordered, r#"[1] < [2]"#, Err(AsaErrorKind::TypeMismatch("Only two numbers or two strings can be compared".to_string())));
+
+// Named arguments
+test_program!(interpreter_named_arguments, r#"fn main(){return sub(b = 1, a = 10);} fn sub(a, b){return a - b;}"#, Ok(Value::Number(9)));
+test_program!(interpreter_positional_then_named, r#"fn main(){return sub(10, b = 4);} fn sub(a, b){return a - b;}"#, Ok(Value::Number(6)));
+test_program!(interpreter_named_argument_unknown, r#"fn main(){return sub(a = 1, c = 2);} fn sub(a, b){return a - b;}"#, Err(AsaErrorKind::Generic("sub has no parameter named c".to_string())));
+test_program!(interpreter_named_argument_twice, r#"fn main(){return sub(1, a = 2);} fn sub(a, b){return a - b;}"#, Err(AsaErrorKind::Generic("The argument a was given more than once".to_string())));
+test_program!(interpreter_named_before_positional, r#"fn main(){return sub(a = 1, 2);} fn sub(a, b){return a - b;}"#, Err(AsaErrorKind::Generic("Positional arguments must come before named arguments".to_string())));
+
+// String normalization builtins
+test_fragment!(builtin_trim, r#"trim("  hi  ")"#, Ok(Value::String("hi".to_string())));
+test_fragment!(builtin_to_upper, r#"to_upper("abc")"#, Ok(Value::String("ABC".to_string())));
+test_fragment!(builtin_to_lower, r#"to_lower("ABC")"#, Ok(Value::String("abc".to_string())));
+test_fragment!(builtin_trim_not_string, r#"trim(1)"#, Err(AsaErrorKind::TypeMismatch("trim expects a string".to_string())));
+
+// Source spans of runtime errors
+#[test]
+fn interpreter_error_span_points_at_failing_subexpression() {
+  let mut interpreter = Interpreter::new();
+  let result = interpreter.load_source(r#"let x = 1 + (2 - true);"#);
+  assert_eq!(result, Err(AsaErrorKind::TypeMismatch("MathExpression operands must be numbers".to_string())));
+  assert_eq!(interpreter.error_span(), Some(Span{start: 13, end: 21}));
+}
+
+#[test]
+fn interpreter_invalid_assignment_target() {
+  let mut interpreter = Interpreter::new();
+  let result = interpreter.load_source(r#"let x = 1; 5 = 1;"#);
+  assert_eq!(result, Err(AsaErrorKind::Generic("invalid assignment target".to_string())));
+  assert_eq!(interpreter.error_span(), Some(Span{start: 11, end: 16}));
+}
+
+#[test]
+fn interpreter_invalid_assignment_target_never_run() {
+  let invalid = Err(AsaErrorKind::Generic("invalid assignment target".to_string()));
+  assert_eq!(Interpreter::new().run_source(r#"fn foo(){return 1;} fn main(){foo() = 1; return 0;}"#), invalid);
+  assert_eq!(Interpreter::new().run_source(r#"fn f(){ 5 = 1; } fn main(){ return 0; }"#), invalid);
+  let mut interpreter = Interpreter::new();
+  assert_eq!(interpreter.run_source(r#"fn main(){ if false { 5 = 1; } return 0; }"#), invalid);
+  assert_eq!(interpreter.error_span(), Some(Span{start: 22, end: 27}));
+  let (_, tree) = program(lex(r#"fn f(){ 5 = 1; } let x = 1; x = 2;"#)).unwrap();
+  assert_eq!(Interpreter::new().check(&tree), vec![AsaErrorKind::Generic("invalid assignment target".to_string())]);
+}
+
+// Loops as expressions
+test_program!(interpreter_while_value, r#"fn main(){let i = 0; let total = 0; let result = while i < 4 { i = i + 1; total = total + i; total }; return result;}"#, Ok(Value::Number(10)));
+test_fragment!(interpreter_while_never_runs, r#"while false { 1; }"#, Ok(Value::Null));
+test_program!(interpreter_while_return, r#"fn main(){let i = 0; while true { i = i + 1; if i == 3 { return i; } } return 0;}"#, Ok(Value::Number(3)));
+test_program!(interpreter_while_let, r#"let n = 0; fn next(){if n < 3 { n = n + 1; n } else { n; }} fn main(){let total = 0; let last = while let x = next() { total = total + x; total * 10 }; return [total, last, is_defined("x")];}"#, Ok(Value::List(vec![
+  Value::Number(6), Value::Number(60), Value::Bool(false),
+])));
+test_program!(interpreter_loop_break_value, r#"fn main(){let n = 0; let total = 0; let result = loop { n = n + 1; total = total + n; if n == 4 { break total; } }; return result;}"#, Ok(Value::Number(10)));
+test_program!(interpreter_break_innermost_loop, r#"fn main(){let i = 0; let inner = loop { loop { break 1; } i = i + 1; if i < 3 { 0 } else { break; } }; let w = while true { i = i + 1; break i; }; return [inner, w];}"#, Ok(Value::List(vec![Value::Null, Value::Number(4)])));
+test_program!(interpreter_break_outside_loop, r#"fn f(){break 1;} fn main(){return loop { f(); };}"#, Err(AsaErrorKind::Generic("break outside of a loop".to_string())));
+// a return in a nested loop leaves the whole function, and only that function
+test_program!(interpreter_nested_while_return, r#"fn main(){let a = find(); let b = find(); return a + b;} fn find(){let i = 0; while i < 5 { let j = 0; while j < 5 { if i * j == 6 { return i * 10 + j; } j = j + 1; } i = i + 1; } return 0;}"#, Ok(Value::Number(46)));
+test_fragment!(builtin_range, r#"range(0, 3)"#, Ok(Value::List(vec![Value::Number(0), Value::Number(1), Value::Number(2)])));
+test_fragment!(builtin_range_empty, r#"range(3, 3)"#, Ok(Value::List(vec![])));
+test_fragment!(builtin_range_reversed, r#"range(3, 1)"#, Ok(Value::List(vec![])));
+test_fragment!(builtin_range_not_number, r#"range(0, "3")"#, Err(AsaErrorKind::TypeMismatch("range expects two numbers".to_string())));
+
+// Static checks
+#[test]
+fn interpreter_check_collects_errors() {
+  let (_, tree) = program(lex(r#"fn main(){let x = y + 1; return foo(1, 2);} fn foo(a){return a;}"#)).unwrap();
+  let interpreter = Interpreter::new();
+  assert_eq!(interpreter.check(&tree), vec![
+    AsaErrorKind::VariableNotDefined("y".to_string()),
+    AsaErrorKind::ArityMismatch{expected: 1, found: 2},
+  ]);
+}
+
+#[test]
+fn interpreter_check_valid_program() {
+  let (_, tree) = program(lex(r#"fn main(){let x = add(a = 1, b = 2); let s = to_upper("x"); return -x;} fn add(a, b){return a + b;}"#)).unwrap();
+  assert_eq!(Interpreter::new().check(&tree), vec![]);
+}
+
+// Raw identifiers
+test_fragment!(interpreter_raw_identifier, r#"let r#if = 1; r#if + 1"#, Ok(Value::Number(2)));
+test_fragment!(interpreter_raw_identifier_plain_name, r#"let r#x = 5; x"#, Ok(Value::Number(5)));
+test_fragment!(interpreter_raw_identifier_in_string, r##""r#if""##, Ok(Value::String("r#if".to_string())));
+
+// Bitwise operators
+test_fragment!(interpreter_bit_and, r#"6 & 3"#, Ok(Value::Number(2)));
+test_fragment!(interpreter_bit_or, r#"6 | 1"#, Ok(Value::Number(7)));
+test_fragment!(interpreter_bit_xor, r#"6 ^ 3"#, Ok(Value::Number(5)));
+test_fragment!(interpreter_shift_left, r#"1 << 4"#, Ok(Value::Number(16)));
+test_fragment!(interpreter_shift_right_negative, r#"-16 >> 2"#, Ok(Value::Number(-4)));
+test_fragment!(interpreter_bitwise_precedence, r#"1 | 2 & 3 == 3 && 1 + 1 << 2 == 8"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_shift_out_of_range, r#"1 << 32"#, Err(AsaErrorKind::Generic("Cannot shift by 32 bits".to_string())));
+test_fragment!(interpreter_bitwise_float, r#"1.5 & 1"#, Err(AsaErrorKind::TypeMismatch("Bitwise operands must be integers".to_string())));
+
+// Multiplication and division, and traced evaluation
+test_fragment!(interpreter_mul_precedence, r#"1 + 2 * 3"#, Ok(Value::Number(7)));
+test_fragment!(interpreter_div_left_assoc, r#"16 / 4 / 2 - 1"#, Ok(Value::Number(1)));
+test_fragment!(interpreter_div_by_zero_parsed, r#"1 / 0"#, Err(AsaErrorKind::DivisionByZero));
+
+#[test]
+fn interpreter_exec_traced() {
+  let (_, tree) = program(lex(r#"let x = 1 + 2 * 3;"#)).unwrap();
+  let mut interpreter = Interpreter::new();
+  let (result, steps) = interpreter.exec_traced(&tree);
+  assert_eq!(result, Ok(Value::Number(7)));
+  assert_eq!(steps, vec![
+    "evaluated 2 * 3 to 6".to_string(),
+    "evaluated 1 + 6 to 7".to_string(),
+    "bound x to 7".to_string(),
+  ]);
+}
+
+// Empty function bodies
+test_program!(interpreter_empty_function_body, r#"fn noop(){} fn main(){let x = noop(); return 5;}"#, Ok(Value::Number(5)));
+test_program!(interpreter_empty_function_body_null, r#"fn noop(){} fn main(){return noop();}"#, Ok(Value::Null));
+
+#[test]
+fn interpreter_unclosed_brace() {
+  let mut interpreter = Interpreter::new();
+  let result = interpreter.load_source("fn main() {\n  return 1;\n");
+  assert_eq!(result, Err(AsaErrorKind::ParseError("Unclosed { at line 1, column 11".to_string())));
+}
+
+#[test]
+fn interpreter_unclosed_call_paren() {
+  let mut interpreter = Interpreter::new();
+  let result = interpreter.load_source("fn main() {\n  return foo(1;\n}");
+  assert_eq!(result, Err(AsaErrorKind::ParseError("Unclosed ( at line 2, column 13".to_string())));
+}
+
+// Match expressions
+test_program!(interpreter_match_number, r#"fn main(){let a = describe(1); let b = describe(2); let c = describe(7); return a + b * 10 + c * 100;} fn describe(n){return match n { 1 => 1, 2 => 2, _ => 3 };}"#, Ok(Value::Number(321)));
+test_fragment!(interpreter_match_string, r#"let x = "b"; match x { "a" => 1, "b" => 2, }"#, Ok(Value::Number(2)));
+test_fragment!(interpreter_match_no_arm, r#"match 3 { 1 => true, 2 => false }"#, Err(AsaErrorKind::NoMatch));
+
+// An undefined operand is reported as such, not as a type error of the operator
+test_fragment!(interpreter_compare_undefined, r#"1 < undefinedvar"#, Err(AsaErrorKind::VariableNotDefined("undefinedvar".to_string())));
+test_fragment!(interpreter_math_undefined, r#"undefinedvar * 2"#, Err(AsaErrorKind::VariableNotDefined("undefinedvar".to_string())));
+test_fragment!(interpreter_logical_undefined, r#"true && undefinedvar"#, Err(AsaErrorKind::VariableNotDefined("undefinedvar".to_string())));
+
+// Backtraces
+#[test]
+fn interpreter_backtrace_lists_calls() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.set_backtrace(true);
+  interpreter.load_source(r#"fn main(){return outer(1);} fn outer(a){let x = 1; return inner(a);} fn inner(b){return b + true;}"#)?;
+  assert_eq!(interpreter.start_main(vec![]), Err(AsaErrorKind::TypeMismatch("MathExpression operands must be numbers".to_string())));
+  assert_eq!(interpreter.backtrace(), Some(&["main".to_string(), "outer".to_string(), "inner".to_string()][..]));
+  Ok(())
+}
+
+#[test]
+fn interpreter_backtrace_off_by_default() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn main(){return nope();}"#)?;
+  assert!(interpreter.start_main(vec![]).is_err());
+  assert_eq!(interpreter.backtrace(), None);
+  Ok(())
+}
+
+// Control flow and assignment at the top level
+test_fragment!(interpreter_top_level_while, r#"let total = 0; let i = 0; while i < 4 { i = i + 1; total = total + i; } total"#, Ok(Value::Number(10)));
+test_fragment!(interpreter_top_level_if, r#"let x = 1; if x > 0 { x = 5; } x"#, Ok(Value::Number(5)));
+test_fragment!(interpreter_if_false_without_else, r#"if false { 1 }"#, Ok(Value::Null));
+test_fragment!(interpreter_assign_undefined, r#"y = 1;"#, Err(AsaErrorKind::VariableNotDefined("y".to_string())));
+test_fragment!(interpreter_top_level_return, r#"let x = 1; return x + 1; x = 5;"#, Ok(Value::Number(2)));
+test_fragment!(interpreter_plus_equal, r#"let total = 0; let i = 0; while i < 4 { i += 1; total += i * 2; } total"#, Ok(Value::Number(20)));
+test_fragment!(interpreter_plus_equal_undefined, r#"y += 1;"#, Err(AsaErrorKind::VariableNotDefined("y".to_string())));
+
+// Reading values from Rust
+#[test]
+fn interpreter_as_list() {
+  let (_, tree) = program(lex(r#"[1, 2, 3]"#)).unwrap();
+  let value = Interpreter::new().exec(&tree).unwrap();
+  let mut sum = 0;
+  for element in value.as_list().unwrap() {
+    if let Value::Number(n) = element {
+      sum += n;
+    }
+  }
+  assert_eq!(sum, 6);
+  assert_eq!(Value::Number(1).as_list(), Err(AsaErrorKind::TypeMismatch("Expected a list".to_string())));
+}
+
+// Type annotations
+test_fragment!(interpreter_annotation_matches, r#"let x: number = 5; let s: string = "a"; x"#, Ok(Value::Number(5)));
+test_fragment!(interpreter_annotation_mismatch, r#"let x: number = true;"#, Err(AsaErrorKind::TypeMismatch("x is declared as a number but was given a bool".to_string())));
+test_program!(interpreter_annotated_parameters, r#"fn main(){return foo(1, false);} fn foo(a: number, b: bool){return a;}"#, Ok(Value::Number(1)));
+test_program!(interpreter_annotated_parameter_mismatch, r#"fn main(){return foo("1", false);} fn foo(a: number, b){return a;}"#, Err(AsaErrorKind::TypeMismatch("a is declared as a number but was given a string".to_string())));
+
+// String formatting
+test_fragment!(builtin_format, r#"format("x = {}", 5)"#, Ok(Value::String("x = 5".to_string())));
+test_fragment!(builtin_format_many, r#"format("{} and {}: {}", "a", 1.5, [1, "b"])"#, Ok(Value::String(r#"a and 1.5: [1, "b"]"#.to_string())));
+test_fragment!(builtin_format_count_mismatch, r#"format("{} {}", 1)"#, Err(AsaErrorKind::Generic("format template has 2 placeholders but was given 1 arguments".to_string())));
+
+// Implicit tail return
+test_program!(interpreter_implicit_tail_return, r#"fn main(){ 1 + 1 }"#, Ok(Value::Number(2)));
+test_program!(interpreter_implicit_tail_return_after_statements, r#"fn main(){ let x = double(3); x + 1 } fn double(a){ let b = a * 2; b }"#, Ok(Value::Number(7)));
+
+// Running a whole program
+#[test]
+fn interpreter_run_source() {
+  assert_eq!(Interpreter::new().run_source(r#"fn main(){return 1 + 1;}"#), Ok(Value::Number(2)));
+  assert_eq!(Interpreter::new().run_source(r#"let x = 3; x * 2"#), Ok(Value::Number(6)));
+}
+
+// Block scopes
+test_program!(interpreter_block_mutates_outer_counter, r#"fn main(){let count = 0; let i = 0; while i < 3 { if i != 1 { count = count + 10; } i = i + 1; } return count;}"#, Ok(Value::Number(20)));
+test_fragment!(interpreter_block_shadowing, r#"let x = 1; if true { let x = 2; x = 3; } x"#, Ok(Value::Number(1)));
+test_fragment!(interpreter_block_locals_end_with_block, r#"if true { let y = 2; } y"#, Err(AsaErrorKind::VariableNotDefined("y".to_string())));
+test_program!(interpreter_function_does_not_see_caller_blocks, r#"fn main(){if true { let x = 1; return f(); } } fn f(){ x }"#, Err(AsaErrorKind::VariableNotDefined("x".to_string())));
+
+// Testing for variables
+test_fragment!(builtin_is_defined, r#"let x = 1; is_defined("x")"#, Ok(Value::Bool(true)));
+test_fragment!(builtin_is_defined_undefined, r#"is_defined("y")"#, Ok(Value::Bool(false)));
+test_program!(builtin_is_defined_outer_block, r#"fn main(){let x = 1; if true { return is_defined("x"); } }"#, Ok(Value::Bool(true)));
+
+// Raw strings
+test_fragment!(interpreter_raw_string, r#"r"a\nb""#, Ok(Value::String("a\\nb".to_string())));
+test_fragment!(interpreter_raw_string_path, r#"let p = r"C:\temp"; p"#, Ok(Value::String(r"C:\temp".to_string())));
+
+// Iterative evaluation
+#[test]
+fn interpreter_iterative_matches_recursive() {
+  let battery = [
+    r#"1 + 2 * 3 - 4 / 2"#,
+    r#"-(1 + 2) * -3"#,
+    r#"1.5 * 2 + 1"#,
+    r#"let x = 5; (x - 1) * (x + 1) == 24 && x != 0 || false"#,
+    r#"let x = 5; x > 1 && x < 10 || x == 0"#,
+    r#"false && 1"#,
+    r#"true || 1"#,
+    r#"true && 1"#,
+    r#"6 & 3 | 8 ^ 1 << 2"#,
+    r#"2147483647 + 1"#,
+    r#"-(-2147483648)"#,
+    r#"1 / 0"#,
+    r#"let s = "b"; s > "a""#,
+    r#"-true"#,
+    r#"1 + undefined"#,
+    r#"let m = {"a": [1, 2]}; let v = m["a"][1]; v * 10"#,
+    r#"fn f(a){ a * 2 } let v = f(3 + 1); v - 1"#,
+  ];
+  for source in battery {
+    let (tokens, tree) = program(lex(source)).unwrap();
+    assert!(tokens.is_done(), "{}", source);
+    let mut recursive = Interpreter::new();
+    let mut iterative = Interpreter::new();
+    iterative.set_iterative(true);
+    let (recursive_result, recursive_trace) = recursive.exec_traced(&tree);
+    let (iterative_result, iterative_trace) = iterative.exec_traced(&tree);
+    assert_eq!(iterative_result, recursive_result, "{}", source);
+    assert_eq!(iterative_trace, recursive_trace, "{}", source);
+    assert_eq!(iterative.error_span(), recursive.error_span(), "{}", source);
+    assert_eq!(iterative.steps(), recursive.steps(), "{}", source);
+  }
+}
+
+#[test]
+fn interpreter_iterative_deep_expression() {
+  // far deeper than the recursive evaluator can go on a test thread's stack
+  let depth = 200_000;
+  let mut tree = Node::Number{value: 0};
+  for _ in 0..depth {
+    tree = Node::Expression{children: vec![Node::MathExpression{name: b"add".to_vec(), children: vec![tree, Node::Number{value: 1}]}]};
+  }
+  let mut interpreter = Interpreter::new();
+  interpreter.set_iterative(true);
+  assert_eq!(interpreter.exec(&tree), Ok(Value::Number(depth)));
+  // dropping a tree this deep recurses, leak it instead
+  std::mem::forget(tree);
+}
+
+#[test]
+fn interpreter_iterative_malformed_nodes() {
+  let span = Span{start: 4, end: 5};
+  let tree = Node::MathExpression{name: b"add".to_vec(), children: vec![Node::Number{value: 1}, Node::Spanned{span, children: vec![]}]};
+  let mut recursive = Interpreter::new();
+  let mut iterative = Interpreter::new();
+  iterative.set_iterative(true);
+  assert_eq!(iterative.exec(&tree), Err(AsaErrorKind::Generic("Spanned must have a child".to_string())));
+  assert_eq!(recursive.exec(&tree), iterative.exec(&tree));
+  assert_eq!(iterative.error_span(), Some(span));
+  assert_eq!(recursive.error_span(), Some(span));
+}
+
+// Doc comments
+#[test]
+fn interpreter_function_doc() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source("/// Adds two numbers,  a and b.\n/// Returns their sum.\nfn add(a, b){ a + b }\n// not documentation\nfn sub(a, b){ a - b }\n/// stray\nlet x = add(1, 2);")?;
+  assert_eq!(interpreter.function_doc("add"), Some("Adds two numbers,  a and b.\nReturns their sum."));
+  assert_eq!(interpreter.function_doc("sub"), None);
+  assert_eq!(interpreter.function_doc("missing"), None);
+  Ok(())
+}
+
+// Flattening Expression wrappers
+#[test]
+fn interpreter_flatten() {
+  let (_, tree) = program(lex(r#"1 + 1"#)).unwrap();
+  let add = Node::MathExpression{name: b"add".to_vec(), children: vec![Node::Number{value: 1}, Node::Number{value: 1}]};
+  assert_eq!(tree, Node::Program{children: vec![Node::Expression{children: vec![add.clone()]}]});
+  let flat = tree.flatten();
+  assert_eq!(flat, Node::Program{children: vec![add]});
+  assert_eq!(Interpreter::new().exec(&flat), Ok(Value::Number(2)));
+}
+
+#[test]
+fn interpreter_flattened_program() {
+  let source = r#"fn main(){ let x = sq((1 + 2)); if x > 5 { return [x, -x]; } 0 } fn sq(a){ return a * a; }"#;
+  let (_, tree) = program(lex(source)).unwrap();
+  let mut interpreter = Interpreter::new();
+  interpreter.exec(&tree.flatten()).unwrap();
+  assert_eq!(interpreter.start_main(vec![]), Ok(Value::List(vec![Value::Number(9), Value::Number(-9)])));
+}
+
+// Function metadata
+#[test]
+fn interpreter_function_metadata() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn foo(a, b, c: number){ return a; } fn bar(){ return 1; }"#)?;
+  assert_eq!(interpreter.function_arity("foo"), Some(3));
+  assert_eq!(interpreter.function_params("foo"), Some(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+  assert_eq!(interpreter.function_arity("bar"), Some(0));
+  assert_eq!(interpreter.function_params("bar"), Some(vec![]));
+  assert_eq!(interpreter.function_arity("baz"), None);
+  assert_eq!(interpreter.function_params("baz"), None);
+  Ok(())
+}
+
+// Source snippets
+#[test]
+fn interpreter_error_snippet() {
+  let source = r#"let y = 2; let x = 3 + y * true;"#;
+  let (_, tree) = program(lex(source)).unwrap();
+  let mut interpreter = Interpreter::new();
+  assert!(interpreter.exec(&tree).is_err());
+  let span = interpreter.error_span().unwrap();
+  assert_eq!(snippet(source, (span.start, span.end)), "y * true");
+}
+
+// Overloading by arity
+test_program!(interpreter_overload_by_arity, r#"fn main(){let a = foo(1); let b = foo(1, 2); return a * 100 + b;} fn foo(a){return a + 10;} fn foo(a, b){return a + b;}"#, Ok(Value::Number(1103)));
+test_program!(interpreter_overload_no_match, r#"fn main(){return foo(1, 2, 3);} fn foo(a){return a;} fn foo(a, b){return a + b;}"#, Err(AsaErrorKind::NoMatchingOverload("foo".to_string())));
+test_program!(interpreter_single_definition_arity_error, r#"fn main(){return foo(1, 2);} fn foo(a){return a;}"#, Err(AsaErrorKind::ArityMismatch{expected: 1, found: 2}));
+
+#[test]
+fn interpreter_overload_load_and_check() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn foo(a){return a;}"#)?;
+  interpreter.load_source(r#"fn foo(a, b){return a + b;}"#)?;
+  assert_eq!(interpreter.load_source(r#"fn foo(x){return x;}"#), Err(AsaErrorKind::DuplicateFunction("foo".to_string())));
+  assert_eq!(interpreter.function_arity("foo"), Some(1));
+  let (_, tree) = program(lex(r#"let a = foo(1); let b = foo(1, 2); let c = foo();"#)).unwrap();
+  assert_eq!(interpreter.check(&tree), vec![AsaErrorKind::NoMatchingOverload("foo".to_string())]);
+  Ok(())
+}
+
+// Zipping lists
+test_fragment!(builtin_zip, r#"zip([1, 2], [3, 4])"#, Ok(Value::List(vec![
+  Value::List(vec![Value::Number(1), Value::Number(3)]),
+  Value::List(vec![Value::Number(2), Value::Number(4)]),
+])));
+test_fragment!(builtin_zip_ragged, r#"zip([1, 2, 3], ["a"])"#, Ok(Value::List(vec![
+  Value::List(vec![Value::Number(1), Value::String("a".to_string())]),
+])));
+test_fragment!(builtin_zip_not_list, r#"zip([1], "a")"#, Err(AsaErrorKind::TypeMismatch("zip expects two lists".to_string())));
+
+// Function values and let rec
+test_program!(interpreter_let_rec_factorial, r#"fn main(){
+  let rec fact = fn(n) {
+    if n <= 1 { return 1; }
+    n * fact(n - 1)
+  };
+  return fact(5);
+}"#, Ok(Value::Number(120)));
+test_program!(interpreter_call_operands, r#"fn fact(n){ if n < 2 { return 1; } return n * fact(n - 1); } fn main(){ return fact(5) + fact(3); }"#, Ok(Value::Number(126)));
+test_program!(interpreter_function_value_without_rec, r#"fn main(){
+  let fact = fn(n) { if n <= 1 { return 1; } n * fact(n - 1) };
+  return fact(3);
+}"#, Err(AsaErrorKind::UndefinedFunction("fact".to_string())));
+test_fragment!(interpreter_function_value_call, r#"let double = fn(x) { x * 2 }; let n = 4; double(n)"#, Ok(Value::Number(8)));
+test_fragment!(interpreter_let_rec_not_function, r#"let rec x = 1;"#, Err(AsaErrorKind::TypeMismatch("let rec must bind a function".to_string())));
+test_fragment!(interpreter_rec_as_name, r#"let rec = 4; rec = rec + 1; let double = fn(x) { x * 2 }; double(rec)"#, Ok(Value::Number(10)));
+test_program!(interpreter_rec_as_function_name, r#"fn rec(n){ n + 1 } fn main(){ let rec r = fn(n) { rec(n) }; return r(1); }"#, Ok(Value::Number(2)));
+
+#[test]
+fn interpreter_check_function_values() {
+  let (_, tree) = program(lex(r#"let rec f = fn(n) { if n < 1 { return 0; } f(n - 1) }; let g = fn(a, b) { a + b }; let a = g(1, 2); let x = f(3); fn h() { let z = x; let y = fn() { z }; return y; }"#)).unwrap();
+  assert_eq!(Interpreter::new().check(&tree), vec![AsaErrorKind::VariableNotDefined("z".to_string())]);
+}
+
+// Keywords inside identifiers
+test_fragment!(interpreter_identifier_starting_with_true, r#"let trueness = 1; trueness"#, Ok(Value::Number(1)));
+test_fragment!(interpreter_identifier_starting_with_false, r#"let falsey = 2; let letter = falsey + 1; letter"#, Ok(Value::Number(3)));
+test_fragment!(interpreter_true_still_boolean, r#"true"#, Ok(Value::Bool(true)));
+
+// Results of every statement
+#[test]
+fn interpreter_exec_all() {
+  let (_, tree) = program(lex(r#"let x = 1; // one
+x + 1; fn double(a){ a * 2 } y; let z = double(x); z"#)).unwrap();
+  assert_eq!(Interpreter::new().exec_all(&tree), vec![
+    Ok(Value::Number(1)),
+    Ok(Value::Number(2)),
+    Ok(Value::Bool(true)),
+    Err(AsaErrorKind::VariableNotDefined("y".to_string())),
+    Ok(Value::Number(2)),
+    Ok(Value::Number(2)),
+  ]);
+}
+
+// List operations
+test_fragment!(builtin_head, r#"head([1, 2, 3])"#, Ok(Value::Number(1)));
+test_fragment!(builtin_head_empty, r#"head([])"#, Err(AsaErrorKind::IndexOutOfBounds));
+test_fragment!(builtin_tail, r#"tail([1, 2, 3])"#, Ok(Value::List(vec![Value::Number(2), Value::Number(3)])));
+test_fragment!(builtin_tail_empty, r#"tail([])"#, Ok(Value::List(vec![])));
+test_fragment!(builtin_append, r#"let xs = [1]; let ys = append(xs, "a"); [xs, ys]"#, Ok(Value::List(vec![
+  Value::List(vec![Value::Number(1)]),
+  Value::List(vec![Value::Number(1), Value::String("a".to_string())]),
+])));
+test_fragment!(builtin_set, r#"set([1, 2, 3], 1, 9)"#, Ok(Value::List(vec![Value::Number(1), Value::Number(9), Value::Number(3)])));
+test_fragment!(builtin_set_out_of_bounds, r#"set([1, 2, 3], 3, 9)"#, Err(AsaErrorKind::IndexOutOfBounds));
+test_fragment!(builtin_set_negative, r#"set([1, 2, 3], 0 - 1, 9)"#, Err(AsaErrorKind::IndexOutOfBounds));
+test_fragment!(builtin_reverse_string, r#"reverse("abé")"#, Ok(Value::String("éba".to_string())));
+test_fragment!(builtin_reverse_list, r#"reverse([1, 2, 3])"#, Ok(Value::List(vec![Value::Number(3), Value::Number(2), Value::Number(1)])));
+test_fragment!(builtin_reverse_not_sequence, r#"reverse(12)"#, Err(AsaErrorKind::TypeMismatch("reverse expects a string or a list".to_string())));
+test_fragment!(builtin_append_not_list, r#"append(1, 2)"#, Err(AsaErrorKind::TypeMismatch("append expects a list and a value".to_string())));
+
+// Numbers in other bases
+test_fragment!(builtin_to_base_hex, r#"to_base(255, 16)"#, Ok(Value::String("ff".to_string())));
+test_fragment!(builtin_to_base_binary, r#"to_base(10, 2)"#, Ok(Value::String("1010".to_string())));
+test_fragment!(builtin_to_base_negative, r#"to_base(-2147483648, 8)"#, Ok(Value::String("-20000000000".to_string())));
+test_fragment!(builtin_to_base_unsupported, r#"to_base(10, 3)"#, Err(AsaErrorKind::Generic("Unsupported base 3, expected 2, 8, 10 or 16".to_string())));
+
+// Malformed nodes, as a hand-built tree might have, are errors rather than panics
+#[test]
+fn interpreter_malformed_nodes() {
+  let mut interpreter = Interpreter::new();
+  assert_eq!(interpreter.exec(&Node::Statement{children: vec![]}), Err(AsaErrorKind::Generic("The statement is empty".to_string())));
+  assert_eq!(interpreter.exec(&Node::FunctionReturn{children: vec![]}), Err(AsaErrorKind::Generic("FunctionReturn must have a value to return".to_string())));
+  let define = Node::FunctionDefine{name: b"f".to_vec(), children: vec![Node::FunctionArguments{children: vec![]}]};
+  assert_eq!(interpreter.exec(&define), Err(AsaErrorKind::Generic("FunctionDefine must have arguments and statements children".to_string())));
+  let spanned = Node::Spanned{span: Span{start: 0, end: 1}, children: vec![]};
+  assert_eq!(interpreter.exec(&spanned), Err(AsaErrorKind::Generic("Spanned must have a child".to_string())));
+  assert_eq!(interpreter.exec(&Node::Expression{children: vec![]}), Err(AsaErrorKind::Generic("Expression must have a child".to_string())));
+}
+
+// Prelude
+#[test]
+fn interpreter_prelude() {
+  let mut interpreter = Interpreter::with_prelude(PRELUDE).unwrap();
+  assert_eq!(interpreter.run_source(r#"fn main(){let x = square(4); return double(x);}"#), Ok(Value::Number(32)));
+  assert!(Interpreter::with_prelude("fn f() {").is_err());
+}
+
+// Snapshots
+#[test]
+fn interpreter_snapshot_restore() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.run_source(r#"let x = 1; let y = "a";"#)?;
+  let snapshot = interpreter.snapshot();
+  interpreter.run_source(r#"x = 5; let z = 2; fn f(){return 3;}"#)?;
+  assert!(interpreter.run_source(r#"y = error("bad");"#).is_err());
+  interpreter.restore(snapshot);
+  assert_eq!(interpreter.run_source(r#"x"#), Ok(Value::Number(1)));
+  assert_eq!(interpreter.run_source(r#"y"#), Ok(Value::String("a".to_string())));
+  assert_eq!(interpreter.run_source(r#"z"#), Err(AsaErrorKind::VariableNotDefined("z".to_string())));
+  assert_eq!(interpreter.function_arity("f"), None);
+  Ok(())
+}
+
+// Precedence: arithmetic binds tighter than comparisons, which bind tighter than && and ||
+test_fragment!(interpreter_mixed_precedence, r#"1 + 2 < 4 && 3 > 1"#, Ok(Value::Bool(true)));
+test_fragment!(interpreter_mixed_precedence_or, r#"2 * 3 == 6 || 1 / 0 > 1"#, Ok(Value::Bool(true)));
+
+// Leftover tokens
+#[test]
+fn interpreter_stray_token_after_function() {
+  let mut interpreter = Interpreter::new();
+  let result = interpreter.run_source("fn main(){return 1;}\n  = 2");
+  assert_eq!(result, Err(AsaErrorKind::ParseError("Unexpected Equal token at line 2, column 3".to_string())));
+}
+
+#[test]
+fn interpreter_parse_failure_points_at_token() {
+  let result = Interpreter::new().run_source("fn main(){ 1 +; }");
+  assert_eq!(result, Err(AsaErrorKind::ParseError("Unexpected Semicolon token at line 1, column 15".to_string())));
+}
+
+// Collecting errors
+#[test]
+fn interpreter_exec_collect_errors() {
+  let (_, tree) = program(lex(r#"let x = 1 / 0; let y = 2; y + 1"#)).unwrap();
+  assert_eq!(Interpreter::new().exec_collect_errors(&tree), (Some(Value::Number(3)), vec![AsaErrorKind::DivisionByZero]));
+}
+
+// Filtering lists with a predicate
+test_fragment!(builtin_filter, r#"filter([1, 2, 3, 4], fn(x) { x > 2 })"#, Ok(Value::List(vec![Value::Number(3), Value::Number(4)])));
+test_fragment!(builtin_count, r#"count([1, 2, 3, 4], fn(x) { x > 2 })"#, Ok(Value::Number(2)));
+test_fragment!(builtin_filter_not_bool, r#"filter([1, 2], fn(x) { x })"#, Err(AsaErrorKind::TypeMismatch("The predicate of filter must return a bool, not a number".to_string())));
+test_fragment!(builtin_count_wrong_arity, r#"count([1, 2], fn(x, y) { true })"#, Err(AsaErrorKind::TypeMismatch("count expects a list and a function of one argument".to_string())));
+
+// Signed arguments
+test_program!(interpreter_signed_arguments, r#"fn main(){return add(-1, +2);} fn add(a, b){return a + b;}"#, Ok(Value::Number(1)));
+test_fragment!(interpreter_unary_plus_variable, r#"let x = 3; 1 - +x"#, Ok(Value::Number(-2)));
+test_fragment!(interpreter_unary_plus_string, r#"let s = "a"; +s"#, Err(AsaErrorKind::TypeMismatch("Only numbers can take a unary plus".to_string())));
+
+// Native functions
+#[test]
+fn interpreter_define_native() {
+  let mut interpreter = Interpreter::new();
+  interpreter.define_native("double", 1, Box::new(|args: &[Value]| match args {
+    [Value::Number(n)] => Ok(Value::Number(n * 2)),
+    _ => Err(AsaErrorKind::TypeMismatch("double expects a number".to_string())),
+  }));
+  assert_eq!(interpreter.run_source(r#"fn main(){let x = double(21); return x;}"#), Ok(Value::Number(42)));
+  assert_eq!(interpreter.run_source(r#"double("a")"#), Err(AsaErrorKind::TypeMismatch("double expects a number".to_string())));
+  assert_eq!(interpreter.run_source(r#"double(1, 2)"#), Err(AsaErrorKind::ArityMismatch{expected: 1, found: 2}));
+}
+
+// Pipes
+test_program!(interpreter_pipe, r#"fn main(){return 5 |> double |> increment;} fn double(x){return x * 2;} fn increment(x){return x + 1;}"#, Ok(Value::Number(11)));
+test_program!(interpreter_pipe_extra_arguments, r#"fn main(){return 1 + 2 |> sub(10);} fn sub(a, b){return a - b;}"#, Ok(Value::Number(-7)));
+test_fragment!(interpreter_pipe_no_parameters, r#"fn zero(){return 0;} 1 |> zero"#, Err(AsaErrorKind::ArityMismatch{expected: 0, found: 1}));
+
+// Dedicated error variants
+test_fragment!(interpreter_math_operand_type_mismatch, r#"let s = "a"; s * 2"#, Err(AsaErrorKind::TypeMismatch("MathExpression operands must be numbers".to_string())));
+test_fragment!(interpreter_index_non_map, r#"let n = 1; n["a"]"#, Err(AsaErrorKind::TypeMismatch("Only maps can be indexed".to_string())));
+test_fragment!(builtin_repeat_type_mismatch, r#"repeat(1, 2)"#, Err(AsaErrorKind::TypeMismatch("repeat expects a string and a number".to_string())));
+test_fragment!(interpreter_lambda_arity_mismatch, r#"let f = fn(a, b) { a }; f(1)"#, Err(AsaErrorKind::ArityMismatch{expected: 2, found: 1}));
+
+#[test]
+fn interpreter_unclosed_bracket_is_parse_error() {
+  let result = Interpreter::new().run_source("fn main() { return [1, 2; }");
+  assert!(matches!(result, Err(AsaErrorKind::ParseError(_))));
+}
+
+// Constants
+test_fragment!(interpreter_const, r#"const PI = 3; PI * 2"#, Ok(Value::Number(6)));
+test_fragment!(interpreter_const_assign, r#"const PI = 3; PI = 4;"#, Err(AsaErrorKind::Generic("cannot reassign constant".to_string())));
+test_fragment!(interpreter_const_let_again, r#"const PI = 3; let PI = 4;"#, Err(AsaErrorKind::Generic("cannot reassign constant".to_string())));
+test_fragment!(interpreter_const_assign_in_block, r#"const PI = 3; if true { PI = 4; }"#, Err(AsaErrorKind::Generic("cannot reassign constant".to_string())));
+test_fragment!(interpreter_const_shadowed_in_block, r#"const PI = 3; let x = 0; if true { let PI = 4; x = PI; } x + PI"#, Ok(Value::Number(7)));
+test_program!(interpreter_const_ends_with_block, r#"fn main(){let n = 0; while n < 2 { const step = 1; n = n + step; } let step = 5; step = 6; return n + step;}"#, Ok(Value::Number(8)));
+
+// Destructuring
+test_program!(interpreter_destructure, r#"fn main(){let [a, b] = [10, 20]; return a + b;}"#, Ok(Value::Number(30)));
+test_fragment!(interpreter_destructure_length_mismatch, r#"let [a, b] = [1, 2, 3];"#, Err(AsaErrorKind::Generic("Cannot destructure a list of 3 elements into 2 names".to_string())));
+test_fragment!(interpreter_destructure_not_list, r#"let [a] = 1;"#, Err(AsaErrorKind::TypeMismatch("Expected a list".to_string())));
+
+// Saturating arithmetic
+test_fragment!(builtin_saturating_add, r#"saturating_add(2147483647, 1)"#, Ok(Value::Number(i32::MAX)));
+test_fragment!(builtin_saturating_sub, r#"saturating_sub(-2147483648, 1)"#, Ok(Value::Number(i32::MIN)));
+test_fragment!(builtin_saturating_add_in_range, r#"saturating_add(2, 3)"#, Ok(Value::Number(5)));
+test_fragment!(builtin_abs_diff, r#"abs_diff(3, 10)"#, Ok(Value::Number(7)));
+test_fragment!(builtin_abs_diff_saturates, r#"abs_diff(-2147483648, 2147483647)"#, Ok(Value::Number(i32::MAX)));
+test_fragment!(builtin_abs_diff_not_integer, r#"abs_diff(1.5, 2)"#, Err(AsaErrorKind::TypeMismatch("abs_diff expects two integers".to_string())));
+
+// Block values
+test_fragment!(interpreter_block_trailing_expression, r#"if true { let x = 1; x + 2 } else { 0 }"#, Ok(Value::Number(3)));
+test_fragment!(interpreter_block_ends_in_statement, r#"if true { let x = 1; x + 2; }"#, Ok(Value::Null));
+test_program!(interpreter_block_value_of_function, r#"fn main(){if false { 1 } else { let x = 2; x * 5 }}"#, Ok(Value::Number(10)));
+
+#[test]
+fn interpreter_block_call_statement() {
+  assert_eq!(Interpreter::new().run_source(r#"fn g(){return 1;} fn main(){ if true { g(); } return 0; }"#), Ok(Value::Number(0)));
+  assert_eq!(Interpreter::new().run_source(r#"fn g(){return 1;} fn main(){ let i = 0; while i < 2 { g(); i = i + 1; } return i; }"#), Ok(Value::Number(2)));
+}
+
+// Type predicates
+test_fragment!(builtin_type, r#"type([1])"#, Ok(Value::String("list".to_string())));
+test_fragment!(builtin_is_number, r#"is_number(1.5)"#, Ok(Value::Bool(true)));
+test_fragment!(builtin_is_number_not, r#"is_number("1")"#, Ok(Value::Bool(false)));
+test_fragment!(builtin_is_string, r#"is_string("a")"#, Ok(Value::Bool(true)));
+test_fragment!(builtin_is_string_not, r#"is_string(true)"#, Ok(Value::Bool(false)));
+test_fragment!(builtin_is_bool, r#"is_bool(false)"#, Ok(Value::Bool(true)));
+test_fragment!(builtin_is_bool_not, r#"is_bool(0)"#, Ok(Value::Bool(false)));
+test_fragment!(builtin_is_bool_arity, r#"is_bool(0, 1)"#, Err(AsaErrorKind::ArityMismatch{expected: 1, found: 2}));
+
+// Operator functions
+test_fragment!(builtin_reduce_add, r#"reduce([1, 2, 3], add, 0)"#, Ok(Value::Number(6)));
+test_fragment!(builtin_reduce_lambda, r#"reduce([1, 2, 3], fn(acc, x) { acc * 10 + x }, 0)"#, Ok(Value::Number(123)));
+test_fragment!(interpreter_operator_function_call, r#"let f = sub; f(10, 4)"#, Ok(Value::Number(6)));
+test_fragment!(builtin_operator_function_direct, r#"mul(3, 4)"#, Ok(Value::Number(12)));
+test_fragment!(builtin_reduce_not_list, r#"reduce(1, add, 0)"#, Err(AsaErrorKind::TypeMismatch("reduce expects a list, a function of two arguments and an initial value".to_string())));
+
+// Globals
+test_program!(interpreter_global_read_by_main, r#"let g = 5; fn main(){return g;}"#, Ok(Value::Number(5)));
+test_program!(interpreter_global_assigned_by_function, r#"fn bump(){g = g + 1; return g;} let g = 1; fn main(){let a = bump(); let b = bump(); return a + b + g;}"#, Ok(Value::Number(8)));
+test_program!(interpreter_global_hidden_by_local, r#"let g = 5; fn main(){let g = 1; return g;}"#, Ok(Value::Number(1)));
+
+#[test]
+fn interpreter_run_source_globals() {
+  assert_eq!(Interpreter::new().run_source(r#"fn main(){return g * 2;} let g = 21;"#), Ok(Value::Number(42)));
+  let (_, tree) = program(lex(r#"fn main(){return g;} let g = 1;"#)).unwrap();
+  assert_eq!(Interpreter::new().check(&tree), vec![]);
+}
+
+// Casts between integers and floats
+test_fragment!(builtin_as_int, r#"as_int(3.9)"#, Ok(Value::Number(3)));
+test_fragment!(builtin_as_int_negative, r#"as_int(-3.9)"#, Ok(Value::Number(-3)));
+test_fragment!(builtin_as_int_overflow, r#"as_int(3000000000.0)"#, Err(AsaErrorKind::NumberOverflow));
+test_fragment!(builtin_as_float, r#"as_float(4)"#, Ok(Value::Float(4.0)));
+test_fragment!(builtin_as_float_not_number, r#"as_float("4")"#, Err(AsaErrorKind::TypeMismatch("as_float expects a number".to_string())));
+
+// JSON rendering of values
+#[test]
+fn interpreter_value_to_json() {
+  assert_eq!(value_to_json(&Value::Number(-3)), "-3");
+  assert_eq!(value_to_json(&Value::Float(1.5)), "1.5");
+  assert_eq!(value_to_json(&Value::Float(f64::NAN)), "null");
+  assert_eq!(value_to_json(&Value::Bool(true)), "true");
+  assert_eq!(value_to_json(&Value::Null), "null");
+  assert_eq!(value_to_json(&Value::String("say \"hi\"\n\\".to_string())), r#""say \"hi\"\n\\""#);
+  assert_eq!(value_to_json(&Value::String("\u{1}".to_string())), r#""\u0001""#);
+  let mut map = std::collections::BTreeMap::new();
+  map.insert("a".to_string(), Value::List(vec![Value::Number(1), Value::List(vec![Value::String("x".to_string())])]));
+  map.insert("b".to_string(), Value::List(vec![]));
+  assert_eq!(value_to_json(&Value::Map(map)), r#"{"a":[1,["x"]],"b":[]}"#);
+}
+
+// Comments between arguments
+test_program!(interpreter_comments_between_arguments, r#"fn sub2(a, b){return a - b;} fn main(){return sub2(10 /* first */, // the second
+  4);}"#, Ok(Value::Number(6)));
+test_program!(interpreter_line_comment_in_expression, "fn main(){ let x = 1 + // note\n 2; return x; }", Ok(Value::Number(3)));
+
+#[test]
+fn interpreter_unterminated_block_comment() {
+  let mut interpreter = Interpreter::new();
+  assert_eq!(interpreter.run_source("fn main(){ return 1; } /* never closed"),
+    Err(AsaErrorKind::ParseError("Unterminated block comment starting at line 1, column 24".to_string())));
+}
+
+// Calling functions from Rust
+#[test]
+fn interpreter_call_with_values() -> Result<(),AsaErrorKind> {
+  let mut interpreter = Interpreter::new();
+  interpreter.load_source(r#"fn add(a, b){return a + b;} fn greet(name: string){return format("hi {}", name);}"#)?;
+  assert_eq!(interpreter.call("add", vec![1.into(), 2.into()]), Ok(Value::Number(3)));
+  assert_eq!(interpreter.call("greet", vec!["asa".into()]), Ok(Value::String("hi asa".to_string())));
+  assert_eq!(interpreter.call("add", vec![1.into()]), Err(AsaErrorKind::ArityMismatch{expected: 2, found: 1}));
+  assert!(matches!(interpreter.call("greet", vec![1.into()]), Err(AsaErrorKind::TypeMismatch(_))));
+  assert_eq!(interpreter.call("missing", vec![]), Err(AsaErrorKind::UndefinedFunction("missing".to_string())));
+  Ok(())
+}
+
+// Hashing values
+test_fragment!(builtin_hash_equal_numbers, r#"[hash(1) == hash(1.0), hash([1]) == hash([1.0]), hash({"a": 1}) == hash({"a": 1.0}), hash(0) == hash(-0.0), hash(1) == hash(1.5)]"#, Ok(Value::List(vec![
+  Value::Bool(true), Value::Bool(true), Value::Bool(true), Value::Bool(true), Value::Bool(false),
+])));
+test_fragment!(builtin_hash_equal_values, r#"[hash([1, "a"]) == hash([1, "a"]), hash(1) == hash(2), hash("1") == hash(1)]"#, Ok(Value::List(vec![
+  Value::Bool(true), Value::Bool(false), Value::Bool(false),
+])));
+
+// The 32 bit FNV-1a hash of some bytes, as hash() returns it.
+fn hash_of(bytes: &[u8]) -> i32 {
+  bytes.iter().fold(0x811c9dc5u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193)) as i32
+}
+
+#[test]
+fn builtin_hash_is_stable() {
+  let hashes: Vec<_> = (0..2).map(|_| Interpreter::new().run_source(r#"hash(1)"#)).collect();
+  assert_eq!(hashes[0], hashes[1]);
+  // the hashed bytes don't depend on the platform, so neither does the hash
+  assert_eq!(hashes[0], Ok(Value::Number(1328750724)));
+  assert_eq!(Interpreter::new().run_source(r#"hash("")"#), Ok(Value::Number(hash_of(&[4, 0, 0, 0, 0, 0, 0, 0, 0]))));
+}
+
+// Reports of a run
+#[test]
+fn interpreter_exec_with_report() {
+  let mut interpreter = Interpreter::new();
+  let tree = program(lex(r#"let x = 6 * 7; print("x is", x); let y = undefined_name;"#)).unwrap().1;
+  let report = interpreter.exec_with_report(&tree);
+  assert_eq!(report.result, Err(AsaErrorKind::VariableNotDefined("undefined_name".to_string())));
+  assert_eq!(report.output, "x is 42\n");
+  assert_eq!(report.warnings, vec!["variable undefined_name is not defined".to_string()]);
+  assert!(report.steps > 0);
+  assert_eq!(report.steps, interpreter.steps());
+
+  let tree = program(lex(r#"print(x + 1); x"#)).unwrap().1;
+  let report = interpreter.exec_with_report(&tree);
+  assert_eq!((report.result, report.output, report.warnings), (Ok(Value::Number(42)), "43\n".to_string(), vec![]));
+}
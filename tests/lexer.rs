@@ -1,132 +1,279 @@
-use asalang::*;
-
-fn test_lex(input: &str) -> Vec<TokenKind> {
-  lex(input).tokens.iter().map(|t| t.get_kind()).collect::<Vec<TokenKind>>()
-}
-
-#[test]
-fn lexer_test_01() {
-  assert_eq!(test_lex("123"),vec![TokenKind::Digit, TokenKind::Digit, TokenKind::Digit, TokenKind::EOF]);
-}
-
-#[test]
-fn lexer_test_02() {
-  assert_eq!(test_lex("abc"),vec![TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::EOF]);
-}
-
-#[test]
-fn lexer_test_03() {
-  assert_eq!(test_lex("hello world"),vec![TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha,  TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::EOF]);
-}
-
-#[test]
-fn lexer_test_04() {
-  assert_eq!(test_lex("true"),vec![TokenKind::True, TokenKind::EOF]);
-}
-
-#[test]
-fn lexer_test_05() {
-  assert_eq!(test_lex("false"),vec![TokenKind::False, TokenKind::EOF]);
-}
-
-#[test]
-fn lexer_test_06() {
-  assert_eq!(test_lex("let x = 123;"),vec![
-    TokenKind::Let, 
-    TokenKind::Alpha, 
-    TokenKind::Equal,
-    TokenKind::Digit,
-    TokenKind::Digit,
-    TokenKind::Digit,
-    TokenKind::Semicolon,
-    TokenKind::EOF,
-  ]);
-}
-
-#[test]
-fn lexer_test_07() {
-  assert_eq!(test_lex(r#"let x = 123;let y="abc";"#),vec![
-    TokenKind::Let, 
-    TokenKind::Alpha, 
-    TokenKind::Equal,
-    TokenKind::Digit,
-    TokenKind::Digit,
-    TokenKind::Digit,
-    TokenKind::Semicolon,
-    TokenKind::Let,
-    TokenKind::Alpha, 
-    TokenKind::Equal,
-    TokenKind::Quote,
-    TokenKind::Alpha, 
-    TokenKind::Alpha, 
-    TokenKind::Alpha, 
-    TokenKind::Quote,
-    TokenKind::Semicolon,
-    TokenKind::EOF,
-  ]);
-}
-
-#[test]
-fn lexer_test_08() {
-  assert_eq!(test_lex(r#"fn main() {}"#),vec![
-    TokenKind::Fn, 
-    TokenKind::Alpha, 
-    TokenKind::Alpha,
-    TokenKind::Alpha,
-    TokenKind::Alpha,
-    TokenKind::LeftParen,
-    TokenKind::RightParen,
-    TokenKind::LeftCurly,
-    TokenKind::RightCurly,
-    TokenKind::EOF,
-  ]);
-}
-
-
-#[test]
-fn lexer_test_09() {
-  assert_eq!(test_lex(r#"fn foo(a,b,c) {
-  let x=a+1;
-	let y=bar(c-b);
-  return x+y;
-}"#),vec![
-    TokenKind::Fn, 
-    TokenKind::Alpha, 
-    TokenKind::Alpha,
-    TokenKind::Alpha,
-    TokenKind::LeftParen,
-    TokenKind::Alpha,
-    TokenKind::Comma,
-    TokenKind::Alpha,
-    TokenKind::Comma,
-    TokenKind::Alpha,
-    TokenKind::RightParen,
-    TokenKind::LeftCurly,
-    TokenKind::Let, 
-    TokenKind::Alpha,
-    TokenKind::Equal,
-    TokenKind::Alpha,
-    TokenKind::Plus,
-    TokenKind::Digit,
-    TokenKind::Semicolon,
-    TokenKind::Let, 
-    TokenKind::Alpha,
-    TokenKind::Equal,
-    TokenKind::Alpha,
-    TokenKind::Alpha,
-    TokenKind::Alpha,
-    TokenKind::LeftParen,
-    TokenKind::Alpha,
-    TokenKind::Dash,
-    TokenKind::Alpha,
-    TokenKind::RightParen,
-    TokenKind::Semicolon,
-    TokenKind::Return, 
-    TokenKind::Alpha,
-    TokenKind::Plus,
-    TokenKind::Alpha,
-    TokenKind::Semicolon,
-    TokenKind::RightCurly,
-    TokenKind::EOF,
-  ]);
-}
\ No newline at end of file
+use asalang::*;
+
+fn test_lex(input: &str) -> Vec<TokenKind> {
+  lex(input).tokens.iter().map(|t| t.get_kind()).collect::<Vec<TokenKind>>()
+}
+
+#[test]
+fn lexer_test_01() {
+  assert_eq!(test_lex("123"),vec![TokenKind::Digit, TokenKind::Digit, TokenKind::Digit, TokenKind::EOF]);
+}
+
+#[test]
+fn lexer_test_02() {
+  assert_eq!(test_lex("abc"),vec![TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::EOF]);
+}
+
+#[test]
+fn lexer_test_03() {
+  assert_eq!(test_lex("hello world"),vec![TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha,  TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::Alpha, TokenKind::EOF]);
+}
+
+#[test]
+fn lexer_test_04() {
+  assert_eq!(test_lex("true"),vec![TokenKind::True, TokenKind::EOF]);
+}
+
+#[test]
+fn lexer_test_05() {
+  assert_eq!(test_lex("false"),vec![TokenKind::False, TokenKind::EOF]);
+}
+
+#[test]
+fn lexer_test_06() {
+  assert_eq!(test_lex("let x = 123;"),vec![
+    TokenKind::Let, 
+    TokenKind::Alpha, 
+    TokenKind::Equal,
+    TokenKind::Digit,
+    TokenKind::Digit,
+    TokenKind::Digit,
+    TokenKind::Semicolon,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_test_07() {
+  assert_eq!(test_lex(r#"let x = 123;let y="abc";"#),vec![
+    TokenKind::Let, 
+    TokenKind::Alpha, 
+    TokenKind::Equal,
+    TokenKind::Digit,
+    TokenKind::Digit,
+    TokenKind::Digit,
+    TokenKind::Semicolon,
+    TokenKind::Let,
+    TokenKind::Alpha, 
+    TokenKind::Equal,
+    TokenKind::Quote,
+    TokenKind::Alpha, 
+    TokenKind::Alpha, 
+    TokenKind::Alpha, 
+    TokenKind::Quote,
+    TokenKind::Semicolon,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_test_08() {
+  assert_eq!(test_lex(r#"fn main() {}"#),vec![
+    TokenKind::Fn, 
+    TokenKind::Alpha, 
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::LeftParen,
+    TokenKind::RightParen,
+    TokenKind::LeftCurly,
+    TokenKind::RightCurly,
+    TokenKind::EOF,
+  ]);
+}
+
+
+#[test]
+fn lexer_test_09() {
+  assert_eq!(test_lex(r#"fn foo(a,b,c) {
+  let x=a+1;
+	let y=bar(c-b);
+  return x+y;
+}"#),vec![
+    TokenKind::Fn, 
+    TokenKind::Alpha, 
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::LeftParen,
+    TokenKind::Alpha,
+    TokenKind::Comma,
+    TokenKind::Alpha,
+    TokenKind::Comma,
+    TokenKind::Alpha,
+    TokenKind::RightParen,
+    TokenKind::LeftCurly,
+    TokenKind::Let, 
+    TokenKind::Alpha,
+    TokenKind::Equal,
+    TokenKind::Alpha,
+    TokenKind::Plus,
+    TokenKind::Digit,
+    TokenKind::Semicolon,
+    TokenKind::Let, 
+    TokenKind::Alpha,
+    TokenKind::Equal,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::LeftParen,
+    TokenKind::Alpha,
+    TokenKind::Dash,
+    TokenKind::Alpha,
+    TokenKind::RightParen,
+    TokenKind::Semicolon,
+    TokenKind::Return, 
+    TokenKind::Alpha,
+    TokenKind::Plus,
+    TokenKind::Alpha,
+    TokenKind::Semicolon,
+    TokenKind::RightCurly,
+    TokenKind::EOF,
+  ]);
+}
+#[test]
+fn lexer_test_10() {
+  let unix = "fn main() {\n  let x = 1;\n  return x;\n}\n";
+  let windows = "fn main() {\r\n  let x = 1;\r\n  return x;\r\n}\r\n";
+  assert_eq!(test_lex(windows), test_lex(unix));
+}
+
+#[test]
+fn lexer_test_11() {
+  // "\r\n", "\n" and a lone "\r" each end exactly one line
+  let lines = lex("a\r\nb\nc\rd").tokens.iter().map(|t| t.start_line).collect::<Vec<u32>>();
+  assert_eq!(lines, vec![1, 2, 3, 4, 4]);
+}
+
+#[test]
+fn lexer_test_12() {
+  assert_eq!(test_lex("1.5==x!=y"),vec![
+    TokenKind::Digit,
+    TokenKind::Dot,
+    TokenKind::Digit,
+    TokenKind::EqualEqual,
+    TokenKind::Alpha,
+    TokenKind::NotEqual,
+    TokenKind::Alpha,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_test_13() {
+  // whitespace is kept inside string literals, including triple-quoted ones
+  assert_eq!(test_lex("\"a b\" \"\"\"x\ny\"\"\""),vec![
+    TokenKind::Quote,
+    TokenKind::Alpha,
+    TokenKind::WhiteSpace,
+    TokenKind::Alpha,
+    TokenKind::Quote,
+    TokenKind::TripleQuote,
+    TokenKind::Alpha,
+    TokenKind::WhiteSpace,
+    TokenKind::Alpha,
+    TokenKind::TripleQuote,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_test_14() {
+  // keywords only match whole words, and two character operators are single tokens
+  assert_eq!(test_lex("if iffy <= 1 && x > 0 else"),vec![
+    TokenKind::If,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::LessEqual,
+    TokenKind::Digit,
+    TokenKind::AndAnd,
+    TokenKind::Alpha,
+    TokenKind::Greater,
+    TokenKind::Digit,
+    TokenKind::Else,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_validate_unterminated_string() {
+  assert_eq!(validate(&lex("let x = \"\"\"abc\n")), Err(AsaErrorKind::ParseError("Unterminated string starting at line 1, column 9".to_string())));
+  assert_eq!(validate(&lex("let x = \"abc\";")), Ok(()));
+}
+
+#[test]
+fn lexer_validate_brackets() {
+  assert_eq!(validate(&lex("fn main() {\n  return 1;\n")), Err(AsaErrorKind::ParseError("Unclosed { at line 1, column 11".to_string())));
+  assert_eq!(validate(&lex("fn main() {\n  return foo(1;\n}")), Err(AsaErrorKind::ParseError("Unclosed ( at line 2, column 13".to_string())));
+  assert_eq!(validate(&lex("let x = 1);")), Err(AsaErrorKind::ParseError("Unmatched ) at line 1, column 10".to_string())));
+  assert_eq!(validate(&lex("let x = \"(\"; // it's [\nlet y = {\"a\": [1]};")), Ok(()));
+}
+
+#[test]
+fn lexer_raw_string() {
+  let kinds: Vec<TokenKind> = lex(r#"r"a" bar"b""#).tokens.iter().map(|token| token.kind).collect();
+  assert_eq!(kinds, vec![
+    TokenKind::RawQuote,
+    TokenKind::Alpha,
+    TokenKind::Quote,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Quote,
+    TokenKind::Alpha,
+    TokenKind::Quote,
+    TokenKind::EOF,
+  ]);
+  assert_eq!(validate(&lex(r#"let x = r"abc;"#)), Err(AsaErrorKind::ParseError("Unterminated string starting at line 1, column 9".to_string())));
+  assert_eq!(validate(&lex("fn main(){ return 1; }\n/* never closed")),
+    Err(AsaErrorKind::ParseError("Unterminated block comment starting at line 2, column 1".to_string())));
+  assert_eq!(validate(&lex("let x = 2 / *y;")), Ok(()));
+}
+
+#[test]
+fn lexer_lexemes_are_source_text() {
+  let source = "fn f() { let s = \"\"\"a  b\"\"\"; s == \"x\" }";
+  for token in lex(source).tokens.iter().filter(|token| token.kind != TokenKind::EOF) {
+    assert_eq!(snippet(source, (token.start_offset, token.end_offset)).as_bytes(), token.lexeme.as_slice());
+  }
+}
+
+#[test]
+fn lexer_snippet_bounds() {
+  assert_eq!(snippet("let é = 1;", (4, 6)), "é");
+  assert_eq!(snippet("let é = 1;", (5, 6)), "é");
+  assert_eq!(snippet("abc", (1, 10)), "bc");
+  assert_eq!(snippet("abc", (5, 2)), "");
+}
+
+#[test]
+fn lexer_two_character_operators() {
+  assert_eq!(test_lex("=="), vec![TokenKind::EqualEqual, TokenKind::EOF]);
+  assert_eq!(test_lex("= ="), vec![TokenKind::Equal, TokenKind::Equal, TokenKind::EOF]);
+  assert_eq!(test_lex("a += 1"), vec![TokenKind::Alpha, TokenKind::PlusEqual, TokenKind::Digit, TokenKind::EOF]);
+  assert_eq!(test_lex("!= <= >= && ||"), vec![TokenKind::NotEqual, TokenKind::LessEqual, TokenKind::GreaterEqual,
+    TokenKind::AndAnd, TokenKind::OrOr, TokenKind::EOF]);
+}
+
+#[test]
+fn lexer_skips_comments_like_whitespace() {
+  assert_eq!(test_lex("1 /* a\n*/ +"), vec![TokenKind::Digit, TokenKind::Plus, TokenKind::EOF]);
+  assert_eq!(test_lex("(1, // note\n2)"), vec![TokenKind::LeftParen, TokenKind::Digit, TokenKind::Comma,
+    TokenKind::Digit, TokenKind::RightParen, TokenKind::EOF]);
+  assert_eq!(test_lex("1 + // note\n2"), vec![TokenKind::Digit, TokenKind::Plus, TokenKind::Digit, TokenKind::EOF]);
+  assert_eq!(test_lex("// (x"), vec![TokenKind::EOF]);
+  // a doc comment is kept for the function that follows, unless it follows something on its line
+  assert_eq!(test_lex("/// (x"), vec![TokenKind::Slash, TokenKind::Slash, TokenKind::Slash, TokenKind::LeftParen,
+    TokenKind::Alpha, TokenKind::EOF]);
+  assert_eq!(test_lex("1 /// x"), vec![TokenKind::Digit, TokenKind::EOF]);
+  let tokens = lex("/* a\nb */ x");
+  assert_eq!((tokens.tokens[0].start_line, tokens.tokens[0].start_col), (2, 6));
+}
+
+#[test]
+fn lexer_keywords_are_whole_words() {
+  let kinds: Vec<TokenKind> = lex("trueness falsey letter fnord returned true").tokens.iter().map(|token| token.kind).collect();
+  assert!(kinds[..kinds.len() - 2].iter().all(|kind| *kind == TokenKind::Alpha));
+  assert_eq!(kinds[kinds.len() - 2..], [TokenKind::True, TokenKind::EOF]);
+}
@@ -1,99 +1,253 @@
-use asalang::*;
-use asalang::Node::*;
-
-macro_rules! test {
-  ($func:ident, $input:tt, $combinator:tt, $test:expr) => (
-    #[test]
-    fn $func() -> Result<(),()> {
-      let source = $input;
-      let tokens = lex(source);
-      let parse_result = $combinator(tokens);
-      match parse_result {
-        Ok((tokens,tree)) => {
-          assert_eq!(tokens.is_done(),true);
-          assert_eq!(tree,$test)
-        },
-        _ => {assert!(false)},
-      }
-      Ok(())
-    }
-  )
-}
-// test name, test string, combinator,  expected result
-test!(parser_ident, r#"hello"#, identifier, Identifier{value: vec![104, 101, 108, 108, 111]});
-test!(parser_number, r#"123"#, number, Number{value: 123});
-test!(parser_bool, r#"true"#, boolean, Bool{value: true});
-test!(parser_string, r#""hello""#, string, String{value: "hello".to_string()});
-test!(parser_function_call, r#"foo()"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
-  FunctionArguments{ children: vec![
-  ]}
-]});
-test!(parser_function_call_one_arg, r#"foo(a)"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
-  FunctionArguments{ children: vec![
-    Expression { children: vec![Identifier { value: vec![97] }]}
-  ]}
-]});
-test!(parser_variable_define_number, r#"let a = 123"#, variable_define, VariableDefine{children: vec![
-  Identifier { value: vec![97] },
-  Expression { children: vec![Number{value: 123 }]}
-]});
-test!(parser_variable_define_bool, r#"let a = true"#, variable_define, VariableDefine{children: vec![
-  Identifier { value: vec![97] },
-  Expression { children: vec![Bool{value: true}]}
-]});
-test!(parser_math_expr, r#"1+1"#, math_expression, MathExpression {name: vec![97, 100, 100], children: vec![
-  Number{value: 1},
-  Number{value: 1}
-]});
-test!(parser_variable_define_math_expr, r#"let a = 1 + 1"#, variable_define, VariableDefine{children: vec![
-  Identifier { value: vec![97] },
-  Expression { children: vec![
-    MathExpression {name: vec![97, 100, 100], children: vec![
-      Number{value: 1},
-      Number{value: 1}
-    ]}
-  ]}
-]});
-test!(parser_variable_function_call, r#"let a = foo()"#, variable_define, VariableDefine{children: vec![
-  Identifier { value: vec![97] },
-  Expression { children: vec![
-    FunctionCall{name: vec![102, 111, 111], children: vec![
-      FunctionArguments{ children: vec![
-      ]}
-    ]}
-  ]}
-]});
-test!(parser_function_define, r#"fn a(){return 1;}"#, function_define, FunctionDefine{
-  name: vec![97],
-  children: vec![
-    FunctionArguments{ children: vec![] },
-    FunctionStatements{ children: vec![
-      FunctionReturn{ children: vec![ 
-        Expression { children: vec![Number{value: 1 }]}
-      ]}
-    ]}
-  ]
-});
-test!(parser_function_define_multi_statements, r#"fn add(a,b){let x=a+b;return x;}"#, function_define, FunctionDefine{
-  name: vec![97, 100, 100],
-  children: vec![
-    FunctionArguments{ children: vec![
-      Expression { children: vec![Identifier { value: vec![97] }] },
-      Expression { children: vec![Identifier { value: vec![98] }] },
-    ] },
-    FunctionStatements{ children: vec![
-      VariableDefine{children: vec![
-        Identifier { value: vec![120] },
-        Expression { children: vec![
-          MathExpression {name: vec![97, 100, 100], children: vec![
-            Identifier{value: vec![97]},
-            Identifier{value: vec![98]}
-          ]}
-        ]}
-      ]},
-      FunctionReturn{ children: vec![ 
-        Expression { children: vec![Identifier{value: vec![120] }]}
-      ]}
-    ]}
-  ]
-});
\ No newline at end of file
+use asalang::*;
+use asalang::Node::*;
+
+macro_rules! test {
+  ($func:ident, $input:tt, $combinator:tt, $test:expr) => (
+    #[test]
+    fn $func() -> Result<(),()> {
+      let source = $input;
+      let tokens = lex(source);
+      let parse_result = $combinator(tokens);
+      match parse_result {
+        Ok((tokens,tree)) => {
+          assert_eq!(tokens.is_done(),true);
+          assert_eq!(tree,$test)
+        },
+        _ => {assert!(false)},
+      }
+      Ok(())
+    }
+  )
+}
+// test name, test string, combinator,  expected result
+test!(parser_ident, r#"hello"#, identifier, Identifier{value: vec![104, 101, 108, 108, 111]});
+test!(parser_number, r#"123"#, number, Number{value: 123});
+test!(parser_bool, r#"true"#, boolean, Bool{value: true});
+test!(parser_string, r#""hello""#, string, String{value: "hello".to_string()});
+test!(parser_function_call, r#"foo()"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![]});
+test!(parser_function_call_one_arg, r#"foo(a)"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
+  Expression { children: vec![Identifier { value: vec![97] }]}
+]});
+test!(parser_function_call_two_args, r#"foo(a, 1)"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
+  Expression { children: vec![Identifier { value: vec![97] }]},
+  Expression { children: vec![Number { value: 1 }]}
+]});
+test!(parser_variable_define_number, r#"let a = 123"#, variable_define, VariableDefine{children: vec![
+  Identifier { value: vec![97] },
+  Expression { children: vec![Number{value: 123 }]}
+]});
+test!(parser_variable_define_bool, r#"let a = true"#, variable_define, VariableDefine{children: vec![
+  Identifier { value: vec![97] },
+  Expression { children: vec![Bool{value: true}]}
+]});
+test!(parser_math_expr, r#"1+1"#, math_expression, MathExpression {name: vec![97, 100, 100], children: vec![
+  Number{value: 1},
+  Number{value: 1}
+]});
+test!(parser_variable_define_math_expr, r#"let a = 1 + 1"#, variable_define, VariableDefine{children: vec![
+  Identifier { value: vec![97] },
+  Expression { children: vec![
+    MathExpression {name: vec![97, 100, 100], children: vec![
+      Number{value: 1},
+      Number{value: 1}
+    ]}
+  ]}
+]});
+test!(parser_variable_function_call, r#"let a = foo()"#, variable_define, VariableDefine{children: vec![
+  Identifier { value: vec![97] },
+  Expression { children: vec![
+    FunctionCall{name: vec![102, 111, 111], children: vec![]}
+  ]}
+]});
+test!(parser_function_define, r#"fn a(){return 1;}"#, function_define, FunctionDefine{
+  name: vec![97],
+  children: vec![
+    FunctionArguments{ children: vec![] },
+    FunctionStatements{ children: vec![
+      FunctionReturn{ children: vec![ 
+        Expression { children: vec![Number{value: 1 }]}
+      ]}
+    ]}
+  ]
+});
+test!(parser_function_define_multi_statements, r#"fn add(a,b){let x=a+b;return x;}"#, function_define, FunctionDefine{
+  name: vec![97, 100, 100],
+  children: vec![
+    FunctionArguments{ children: vec![
+      Expression { children: vec![Identifier { value: vec![97] }] },
+      Expression { children: vec![Identifier { value: vec![98] }] },
+    ] },
+    FunctionStatements{ children: vec![
+      VariableDefine{children: vec![
+        Identifier { value: vec![120] },
+        Expression { children: vec![
+          MathExpression {name: vec![97, 100, 100], children: vec![
+            Identifier{value: vec![97]},
+            Identifier{value: vec![98]}
+          ]}
+        ]}
+      ]},
+      FunctionReturn{ children: vec![ 
+        Expression { children: vec![Identifier{value: vec![120] }]}
+      ]}
+    ]}
+  ]
+});
+test!(parser_map_literal, r#"{"a": 1, "b": true}"#, map_literal, Map{children: vec![
+  MapEntry{children: vec![String{value: "a".to_string()}, Expression{children: vec![Number{value: 1}]}]},
+  MapEntry{children: vec![String{value: "b".to_string()}, Expression{children: vec![Bool{value: true}]}]},
+]});
+test!(parser_index_expression, r#"m["a"]"#, index_expression, Index{children: vec![
+  Identifier{value: vec![109]},
+  Expression{children: vec![String{value: "a".to_string()}]},
+]});
+test!(parser_paren_expression, r#"((1))"#, expression, Expression{children: vec![
+  Expression{children: vec![
+    Expression{children: vec![Number{value: 1}]}
+  ]}
+]});
+test!(parser_math_expr_parens, r#"(1+2)-3"#, math_expression, MathExpression {name: vec![115, 117, 98], children: vec![
+  Expression{children: vec![
+    MathExpression {name: vec![97, 100, 100], children: vec![Number{value: 1}, Number{value: 2}]}
+  ]},
+  Number{value: 3}
+]});
+
+#[test]
+fn parser_unbalanced_parens_terminate() {
+  // one closing paren short: must fail (or stop early) rather than loop
+  if let Ok((tokens, _)) = program(lex("((((1)))")) {
+    assert!(!tokens.is_done());
+  }
+}
+
+#[test]
+fn parser_deeply_nested_parens() {
+  let depth = 64;
+  let source = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+  let (tokens, _) = expression(lex(&source)).unwrap();
+  assert!(tokens.is_done());
+}
+test!(parser_float, r#"1.25"#, number, Float{value: 1.25});
+test!(parser_equality, r#"a == 1"#, expression, Expression{children: vec![
+  MathExpression {name: vec![101, 113], children: vec![Identifier{value: vec![97]}, Number{value: 1}]}
+]});
+test!(parser_call_left_operand, r#"f(1) + 2"#, expression, Expression{children: vec![
+  MathExpression {name: b"add".to_vec(), children: vec![
+    FunctionCall{name: b"f".to_vec(), children: vec![Expression{children: vec![Number{value: 1}]}]}, Number{value: 2},
+  ]}
+]});
+test!(parser_call_right_operand, r#"2 + f(1)"#, expression, Expression{children: vec![
+  MathExpression {name: b"add".to_vec(), children: vec![
+    Number{value: 2}, FunctionCall{name: b"f".to_vec(), children: vec![Expression{children: vec![Number{value: 1}]}]},
+  ]}
+]});
+test!(parser_index_operand, r#"2 * xs[0]"#, expression, Expression{children: vec![
+  MathExpression {name: b"mul".to_vec(), children: vec![
+    Number{value: 2}, Index{children: vec![Identifier{value: b"xs".to_vec()}, Expression{children: vec![Number{value: 0}]}]},
+  ]}
+]});
+test!(parser_negative_number, r#"-2147483648"#, value, Number{value: -2147483648});
+test!(parser_negation, r#"-x"#, value, UnaryExpression{name: vec![110, 101, 103], children: vec![Identifier{value: vec![120]}]});
+
+#[test]
+fn parser_number_out_of_range() {
+  assert!(number(lex("2147483648")).is_err());
+}
+test!(parser_ident_underscore, r#"_to_x1"#, identifier, Identifier{value: b"_to_x1".to_vec()});
+test!(parser_if_without_parens, r#"if x < 5 && y > 0 { return 1; }"#, if_expression, If{children: vec![
+  Expression{children: vec![MathExpression{name: b"and".to_vec(), children: vec![
+    MathExpression{name: b"lt".to_vec(), children: vec![Identifier{value: b"x".to_vec()}, Number{value: 5}]},
+    MathExpression{name: b"gt".to_vec(), children: vec![Identifier{value: b"y".to_vec()}, Number{value: 0}]},
+  ]}]},
+  Block{children: vec![Statement{children: vec![FunctionReturn{children: vec![Expression{children: vec![Number{value: 1}]}]}]}]},
+]});
+test!(parser_list_literal, r#"[1, x]"#, list_literal, List{children: vec![
+  Expression{children: vec![Number{value: 1}]},
+  Expression{children: vec![Identifier{value: b"x".to_vec()}]},
+]});
+
+#[test]
+fn parser_spans() {
+  let (tokens, tree) = expression(lex(r#"a < m["k"]"#)).unwrap();
+  assert!(tokens.is_done());
+  match &tree.children()[0] {
+    Spanned{span, children} => {
+      assert_eq!(*span, Span{start: 0, end: 10});
+      // spans are ignored by ==, so check the index's span by matching on it
+      assert!(matches!(children[0].children()[1], Spanned{span: Span{start: 4, end: 10}, ..}));
+    },
+    other => panic!("expected a spanned node, got {:?}", other),
+  }
+}
+test!(parser_term_precedence, r#"1 + 2 * 3"#, math_expression, MathExpression{name: b"add".to_vec(), children: vec![
+  Number{value: 1},
+  MathExpression{name: b"mul".to_vec(), children: vec![Number{value: 2}, Number{value: 3}]},
+]});
+
+#[test]
+fn parser_parse_partial() {
+  let (tree, leftovers) = parse_partial(r#"fn main(){return 1;} let x = ;"#);
+  assert_eq!(tree, Some(Program{children: vec![FunctionDefine{name: b"main".to_vec(), children: vec![
+    FunctionArguments{children: vec![]},
+    FunctionStatements{children: vec![FunctionReturn{children: vec![Expression{children: vec![Number{value: 1}]}]}]},
+  ]}]}));
+  let kinds: Vec<TokenKind> = leftovers.iter().map(|token| token.kind).collect();
+  assert_eq!(kinds, vec![TokenKind::Let, TokenKind::Alpha, TokenKind::Equal, TokenKind::Semicolon]);
+
+  let (tree, leftovers) = parse_partial(r#"1 + 1"#);
+  assert!(tree.is_some());
+  assert!(leftovers.is_empty());
+  assert_eq!(parse_partial(r#")"#).0, None);
+}
+test!(parser_variable_define_annotated, r#"let a: number = 1"#, variable_define, VariableDefine{children: vec![
+  Identifier{value: vec![97]},
+  Expression{children: vec![Number{value: 1}]},
+  TypeAnnotation{value: b"number".to_vec()},
+]});
+test!(parser_mixed_precedence, r#"1 + 2 < 4 && 3 > 1"#, expression, Expression{children: vec![MathExpression{name: b"and".to_vec(), children: vec![
+  MathExpression{name: b"lt".to_vec(), children: vec![
+    MathExpression{name: b"add".to_vec(), children: vec![Number{value: 1}, Number{value: 2}]},
+    Number{value: 4},
+  ]},
+  MathExpression{name: b"gt".to_vec(), children: vec![Number{value: 3}, Number{value: 1}]},
+]}]});
+test!(parser_unary_plus, r#"+2"#, value, Number{value: 2});
+test!(parser_pipe, r#"x |> f(1) |> g"#, expression, Expression{children: vec![
+  FunctionCall{name: b"g".to_vec(), children: vec![Expression{children: vec![
+    FunctionCall{name: b"f".to_vec(), children: vec![
+      Expression{children: vec![Identifier{value: b"x".to_vec()}]},
+      Expression{children: vec![Number{value: 1}]},
+    ]}
+  ]}]}
+]});
+test!(parser_const_define, r#"const a = 1"#, const_define, ConstDefine{children: vec![
+  Identifier{value: vec![97]},
+  Expression{children: vec![Number{value: 1}]},
+]});
+
+#[test]
+fn parser_remaining_tokens() {
+  let (tokens, _) = program(lex(r#"fn main(){return 1;} let x = ;"#)).unwrap();
+  assert_eq!(remaining_tokens(&tokens), 4);
+  assert_eq!(first_remaining_kind(&tokens), Some(TokenKind::Let));
+  let (tokens, _) = program(lex(r#"fn main(){return 1;}"#)).unwrap();
+  assert_eq!(remaining_tokens(&tokens), 0);
+  assert_eq!(first_remaining_kind(&tokens), None);
+}
+test!(parser_destructure, r#"let [a, b] = x"#, variable_define, Destructure{children: vec![
+  Identifier{value: vec![97]},
+  Identifier{value: vec![98]},
+  Expression{children: vec![Identifier{value: vec![120]}]},
+]});
+test!(parser_block_trailing_expression, r#"{ let x = 1; x }"#, block, Block{children: vec![
+  Statement{children: vec![VariableDefine{children: vec![Identifier{value: vec![120]}, Expression{children: vec![Number{value: 1}]}]}]},
+  Expression{children: vec![Identifier{value: vec![120]}]},
+]});
+test!(parser_plus_equal, r#"x += 1"#, assignment, Assignment{children: vec![
+  Identifier{value: vec![120]},
+  Expression{children: vec![MathExpression{name: b"add".to_vec(), children: vec![
+    Identifier{value: vec![120]},
+    Expression{children: vec![Number{value: 1}]}
+  ]}]}
+]});
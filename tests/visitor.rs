@@ -0,0 +1,36 @@
+use asalang::*;
+
+struct CallCounter {
+  calls: usize,
+}
+
+impl Visitor for CallCounter {
+  fn visit_function_call(&mut self, node: &Node) {
+    self.calls += 1;
+    walk(self, node);
+  }
+}
+
+#[test]
+fn visitor_counts_function_calls() {
+  let (_, tree) = program(lex(r#"fn foo(a,b,c) {
+  let x = bar(a);
+  let y = baz(bar(b), c);
+  return x + y;
+}
+fn main() {
+  return foo(1,2,3);
+}"#)).unwrap();
+  let mut counter = CallCounter{calls: 0};
+  counter.visit_node(&tree);
+  assert_eq!(counter.calls, 4);
+}
+
+#[test]
+fn visitor_walks_without_overrides() {
+  // the default hooks walk through the definition and the map literal to the call inside it
+  let (_, tree) = program(lex(r#"let m = {"a": f(1)};"#)).unwrap();
+  let mut counter = CallCounter{calls: 0};
+  counter.visit_node(&tree);
+  assert_eq!(counter.calls, 1);
+}